@@ -51,29 +51,255 @@ How many steps does it now take to reach the exit?
 
 extern crate aoc;
 
+use std::fmt;
+
 fn main() {
     let mut input = aoc::ProgramInput::new(PUZZLE, INPUT);
     println!("Day 5: {}", PUZZLE);
 
-    let mut list = input.to_str()
+    let list = input.to_str()
         .lines()
         .map(|x| x.parse::<isize>().unwrap())
         .collect::<Vec<_>>();
-    let n = list.len() as isize;
-    let mut i = 0;
-    let mut lost = 0;
-    loop {
-        let jmp = list[i];
-        // todo: day 1 and 2
-        list[i] += if jmp >= 3 { -1 } else { 1 };
-        lost += 1;
-        if (i as isize) + jmp >= n {
-            break;
-        } else {
-            i = ((i as isize) + jmp) as usize;
+
+    match JumpMaze::new(list.clone(), Increment).run() {
+        JumpOutcome::ExitedEnd(state) => println!(":: Answer 1 is {}", state.steps),
+        outcome => println!(":: Answer 1 could not be calculated: {}", outcome),
+    }
+    match JumpMaze::new(list, Threshold(3)).run() {
+        JumpOutcome::ExitedEnd(state) => println!(":: Answer 2 is {}", state.steps),
+        outcome => println!(":: Answer 2 could not be calculated: {}", outcome),
+    }
+}
+
+/// An `OffsetRule` determines how an offset is modified after it has been jumped.
+///
+/// Any closure `Fn(isize) -> isize` can be used as a rule as well.
+pub trait OffsetRule {
+    /// Returns the new offset, given the offset that was just used for a jump.
+    fn next(&self, offset: isize) -> isize;
+}
+
+/// The rule from part one: always increase the offset by 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Increment;
+
+impl OffsetRule for Increment {
+    fn next(&self, offset: isize) -> isize {
+        offset.saturating_add(1)
+    }
+}
+
+/// The rule from part two: if the offset is at least the threshold, decrease
+/// it by 1, otherwise increase it by 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Threshold(pub isize);
+
+impl OffsetRule for Threshold {
+    fn next(&self, offset: isize) -> isize {
+        if offset >= self.0 { offset.saturating_sub(1) } else { offset.saturating_add(1) }
+    }
+}
+
+impl<F> OffsetRule for F
+where F: Fn(isize) -> isize {
+    fn next(&self, offset: isize) -> isize {
+        self(offset)
+    }
+}
+
+/// A maze of jump offsets together with the rule to modify them.
+#[derive(Debug)]
+pub struct JumpMaze<R: OffsetRule> {
+    offsets: Vec<isize>,
+    rule: R,
+    budget: Option<usize>,
+}
+
+impl<R: OffsetRule> JumpMaze<R> {
+    pub fn new(offsets: Vec<isize>, rule: R) -> Self {
+        JumpMaze {
+            offsets,
+            rule,
+            budget: None,
+        }
+    }
+
+    /// Limits the number of steps that `run` may take before giving up.
+    pub fn with_budget(mut self, steps: usize) -> Self {
+        self.budget = Some(steps);
+        self
+    }
+
+    pub fn offsets(&self) -> &[isize] {
+        &self.offsets
+    }
+
+    /// Follows the jumps from the first instruction until one leads outside
+    /// the list, in either direction, or until the step budget is used up.
+    ///
+    /// The maze itself is not modified, so it can be run multiple times.
+    pub fn run(&self) -> JumpOutcome {
+        let mut list = self.offsets.clone();
+        let mut i = 0;
+        let mut steps = 0;
+        if list.is_empty() {
+            return JumpOutcome::ExitedEnd(JumpState::new(steps, i, list));
+        }
+        loop {
+            if self.budget.is_some_and(|b| steps >= b) {
+                return JumpOutcome::BudgetExceeded(JumpState::new(steps, i, list));
+            }
+
+            let jmp = list[i];
+            list[i] = self.rule.next(jmp);
+            steps += 1;
+
+            // The target is computed in isize, so that we can tell in which
+            // direction we left the list. An overflow can only happen when
+            // the jump is so far that it is outside the list anyway.
+            match (i as isize).checked_add(jmp) {
+                Some(j) if j < 0 => {
+                    return JumpOutcome::ExitedStart(JumpState::new(steps, i, list));
+                }
+                Some(j) if j as usize >= list.len() => {
+                    return JumpOutcome::ExitedEnd(JumpState::new(steps, i, list));
+                }
+                Some(j) => i = j as usize,
+                None if jmp < 0 => {
+                    return JumpOutcome::ExitedStart(JumpState::new(steps, i, list));
+                }
+                None => {
+                    return JumpOutcome::ExitedEnd(JumpState::new(steps, i, list));
+                }
+            }
+        }
+    }
+}
+
+/// The state of a maze after `JumpMaze::run` has finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpState {
+    /// Number of jumps that were taken.
+    pub steps: usize,
+
+    /// Index of the last instruction that was jumped from, or in case the
+    /// budget was exceeded, the index of the next instruction.
+    pub position: usize,
+
+    /// The offsets as they were left behind.
+    pub offsets: Vec<isize>,
+}
+
+impl JumpState {
+    fn new(steps: usize, position: usize, offsets: Vec<isize>) -> Self {
+        JumpState {
+            steps,
+            position,
+            offsets,
+        }
+    }
+}
+
+/// The way in which running a maze terminated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpOutcome {
+    /// A jump led past the end of the list.
+    ExitedEnd(JumpState),
+
+    /// A jump led before the start of the list.
+    ExitedStart(JumpState),
+
+    /// The step budget was used up before the maze was exited.
+    BudgetExceeded(JumpState),
+}
+
+impl JumpOutcome {
+    pub fn state(&self) -> &JumpState {
+        match *self {
+            JumpOutcome::ExitedEnd(ref s) => s,
+            JumpOutcome::ExitedStart(ref s) => s,
+            JumpOutcome::BudgetExceeded(ref s) => s,
         }
     }
-    println!(":: Answer 2 is {}", lost);
+
+    pub fn into_state(self) -> JumpState {
+        match self {
+            JumpOutcome::ExitedEnd(s) => s,
+            JumpOutcome::ExitedStart(s) => s,
+            JumpOutcome::BudgetExceeded(s) => s,
+        }
+    }
+
+    pub fn steps(&self) -> usize {
+        self.state().steps
+    }
+
+    /// Returns true if the maze was exited in either direction.
+    pub fn is_exit(&self) -> bool {
+        !matches!(*self, JumpOutcome::BudgetExceeded(..))
+    }
+}
+
+impl fmt::Display for JumpOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JumpOutcome::ExitedEnd(ref s) => write!(f, "exited past the end after {} steps from index {}", s.steps, s.position),
+            JumpOutcome::ExitedStart(ref s) => write!(f, "exited before the start after {} steps from index {}", s.steps, s.position),
+            JumpOutcome::BudgetExceeded(ref s) => write!(f, "exceeded budget of {} steps at index {}", s.steps, s.position),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increment() {
+        let maze = JumpMaze::new(vec![0, 3, 0, 1, -3], Increment);
+        assert_eq!(maze.run(), JumpOutcome::ExitedEnd(JumpState::new(5, 1, vec![2, 5, 0, 1, -2])));
+    }
+
+    #[test]
+    fn test_threshold() {
+        let maze = JumpMaze::new(vec![0, 3, 0, 1, -3], Threshold(3));
+        assert_eq!(maze.run(), JumpOutcome::ExitedEnd(JumpState::new(10, 3, vec![2, 3, 2, 3, -1])));
+    }
+
+    #[test]
+    fn test_closure() {
+        let maze = JumpMaze::new(vec![0, 3, 0, 1, -3], |x| x + 1);
+        assert_eq!(maze.run().steps(), 5);
+    }
+
+    #[test]
+    fn test_exit_start() {
+        let tests = vec![
+            (vec![1, -2], 2, 1),
+            (vec![-1], 1, 0),
+            (vec![isize::MIN], 1, 0),
+        ];
+
+        for t in tests {
+            match JumpMaze::new(t.0.clone(), Increment).run() {
+                JumpOutcome::ExitedStart(s) => assert_eq!((s.steps, s.position), (t.1, t.2), "input = {:?}", t.0),
+                outcome => panic!("unexpected outcome for {:?}: {}", t.0, outcome),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exit_end_overflow() {
+        let maze = JumpMaze::new(vec![1, isize::MAX], Increment);
+        assert_eq!(maze.run(), JumpOutcome::ExitedEnd(JumpState::new(2, 1, vec![2, isize::MAX])));
+    }
+
+    #[test]
+    fn test_budget() {
+        let maze = JumpMaze::new(vec![1, -1], |x| x).with_budget(100);
+        assert_eq!(maze.run(), JumpOutcome::BudgetExceeded(JumpState::new(100, 0, vec![1, -1])));
+    }
 }
 
 const PUZZLE: &'static str = "A Maze of Twisty Trampolines, All Alike";
@@ -53,101 +53,198 @@ How many cycles are in the infinite loop that arises from the configuration in y
 */
 
 extern crate aoc;
+extern crate clap;
 
-use std::collections::HashMap;
+use clap::Arg;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 
 fn main() {
-    let mut input = aoc::ProgramInput::new(PUZZLE, INPUT);
+    let mut input = aoc::ProgramInput::with_args(PUZZLE, INPUT, vec![
+        Arg::with_name("trace")
+            .long("trace")
+            .value_name("FILE")
+            .help("Write every state of the banks as TSV to FILE"),
+    ]);
     println!("Day 6: {}", PUZZLE);
 
+    let verbose = input.verbosity() > 0;
+    let mut trace = input.value_of("trace").map(|path| {
+        BufWriter::new(File::create(path).expect("cannot create trace file"))
+    });
+
     let nums: Vec<u32> = input.to_str()
         .split_whitespace()
         .map(|x| x.parse::<u32>().unwrap())
         .collect();
-    let mut mem = Memory::from_iter(nums.iter());
-
-    let mut count = 0;
-    println!("Balancing:");
-    while !mem.is_known() {
-        mem.balance();
-        println!(" -> {}", mem);
-        count += 1;
+    let mem = Memory::from_iter(nums.iter());
+
+    if let Some(ref mut w) = trace {
+        write_trace_header(w, mem.banks().len()).expect("cannot write trace file");
+        write_trace_row(w, 0, None, mem.banks()).expect("cannot write trace file");
     }
+    if verbose {
+        println!("Balancing:");
+    }
+    let mut cycle = 0;
+    let (mu, lambda) = aoc::find_cycle(mem.banks().to_vec(), |banks| {
+        let next = redistribute(banks);
+        cycle += 1;
+        if verbose {
+            println!(" -> {}", Memory::new(next.clone()));
+        }
+        if let Some(ref mut w) = trace {
+            write_trace_row(w, cycle, Some(donor(banks)), &next).expect("cannot write trace file");
+        }
+        next
+    });
+
+    println!(":: Answer 1 is {}", mu + lambda);
+    println!(":: Answer 2 is {}", lambda);
+}
 
-    println!(":: Answer 1 is {}", count);
-    println!(":: Answer 2 is {}", mem.known_from().unwrap());
+/// Writes the TSV header for a trace of `n` banks.
+fn write_trace_header<W: Write>(w: &mut W, n: usize) -> io::Result<()> {
+    write!(w, "cycle\tdonor")?;
+    for i in 0..n {
+        write!(w, "\tbank{}", i)?;
+    }
+    writeln!(w)
 }
 
-#[derive(Debug)]
-pub struct Memory{
+/// Writes one state of the banks as a TSV row.
+///
+/// The donor is the bank that was redistributed to reach this state;
+/// it is left empty for the initial state.
+fn write_trace_row<W: Write>(w: &mut W, cycle: usize, donor: Option<usize>, banks: &[u32]) -> io::Result<()> {
+    write!(w, "{}\t", cycle)?;
+    if let Some(d) = donor {
+        write!(w, "{}", d)?;
+    }
+    for x in banks {
+        write!(w, "\t{}", x)?;
+    }
+    writeln!(w)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory {
     banks: Vec<u32>,
-    balanced: u32,
-    history: HashMap<String, u32>,
 }
 
 impl Memory {
-    pub fn from_iter<'a, I: Iterator<Item = &'a u32>>(it: I)-> Self {
-        Memory{
-            banks: it.map(|x| *x).collect(),
-            balanced: 0,
-            history: HashMap::new(),
-        }
+    pub fn new(banks: Vec<u32>) -> Self {
+        Memory { banks }
+    }
+
+    pub fn from_iter<'a, I: Iterator<Item = &'a u32>>(it: I) -> Self {
+        Memory::new(it.cloned().collect())
+    }
+
+    pub fn banks(&self) -> &[u32] {
+        &self.banks
     }
 
     pub fn balance(&mut self) {
-        self.register();
-        let (mut idx, mut max) = self.banks.iter().enumerate().fold((0, 0), |acc, x| {
-            if *x.1 > acc.1 {
-                (x.0, *x.1)
-            } else {
-                acc
-            }
-        });
-
-        let n = self.banks.len();
-        self.banks[idx] = 0;
-        while max != 0 {
-            idx = (idx + 1) % n;
-            self.banks[idx] += 1;
-            max -= 1;
-        }
-        self.balanced += 1;
+        self.banks = redistribute(&self.banks);
     }
 
-    pub fn is_known(&self) -> bool {
-        self.history.contains_key(&self.to_string())
+    /// Returns `(mu, lambda)` for the sequence of balancing operations,
+    /// where `mu` is the number of operations before the first state
+    /// in the cycle, and `lambda` is the length of the cycle.
+    pub fn cycle(&self) -> (usize, usize) {
+        aoc::find_cycle(self.banks.clone(), |banks| redistribute(banks))
     }
+}
 
-    pub fn known_from(&self) -> Option<u32> {
-        match self.history.get(&self.to_string()) {
-            Some(n) => Some(self.balanced - *n),
-            None => None,
+/// Returns the index of the bank with the most blocks, which is the one
+/// that gets redistributed next.
+///
+/// Ties are won by the lowest-numbered bank.
+pub fn donor(banks: &[u32]) -> usize {
+    banks.iter().enumerate().fold((0, 0), |acc, x| {
+        if *x.1 > acc.1 {
+            (x.0, *x.1)
+        } else {
+            acc
         }
-    }
+    }).0
+}
 
-    fn register(&mut self) {
-        let state = self.to_string();
-        self.history.insert(state, self.balanced);
+/// Redistributes the blocks of the donor bank to all other banks.
+pub fn redistribute(banks: &[u32]) -> Vec<u32> {
+    let mut banks = banks.to_vec();
+    let mut idx = donor(&banks);
+    let mut max = banks[idx];
+
+    let n = banks.len();
+    banks[idx] = 0;
+    while max != 0 {
+        idx = (idx + 1) % n;
+        banks[idx] += 1;
+        max -= 1;
     }
+    banks
 }
 
 impl fmt::Display for Memory {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut output = String::new();
-        output.push_str("[");
+        output.push('[');
         for x in self.banks.iter() {
             output.push_str(x.to_string().as_str());
-            output.push_str(" ");
+            output.push(' ');
         }
         if output.len() > 1 {
             output.pop();
         }
-        output.push_str("]");
+        output.push(']');
         write!(f, "{} ", output)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance() {
+        let mut mem = Memory::new(vec![0, 2, 7, 0]);
+        let expect = vec![
+            vec![2, 4, 1, 2],
+            vec![3, 1, 2, 3],
+            vec![0, 2, 3, 4],
+            vec![1, 3, 4, 1],
+            vec![2, 4, 1, 2],
+        ];
+
+        for t in expect {
+            mem.balance();
+            assert_eq!(mem.banks(), t.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut buf = Vec::new();
+        write_trace_header(&mut buf, 4).unwrap();
+        write_trace_row(&mut buf, 0, None, &[0, 2, 7, 0]).unwrap();
+        write_trace_row(&mut buf, 1, Some(2), &[2, 4, 1, 2]).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "cycle\tdonor\tbank0\tbank1\tbank2\tbank3\n0\t\t0\t2\t7\t0\n1\t2\t2\t4\t1\t2\n"
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mem = Memory::new(vec![0, 2, 7, 0]);
+        assert_eq!(mem.cycle(), (1, 4));
+        assert_eq!(aoc::find_cycle_brent(mem.banks().to_vec(), |b| redistribute(b)), (1, 4));
+    }
+}
+
 const PUZZLE: &'static str = "Memory Reallocation";
 const INPUT: &'static str = r"
 0	5	10	0	11	14	13	4	11	8	8	7	1	4	12	11
@@ -96,127 +96,215 @@ entire tower?
 */
 
 extern crate aoc;
-extern crate regex;
-#[macro_use] extern crate lazy_static;
+extern crate clap;
 
-use regex::Regex;
-use std::{fmt, error};
+use aoc::tree;
+use clap::Arg;
+use std::{fmt, error, vec};
+use std::cell::Ref;
+use std::fs::File;
+use std::io::Write;
 use std::str::FromStr;
 use std::collections::HashMap;
 
 fn main() {
-    let mut input = aoc::ProgramInput::new(PUZZLE, INPUT);
+    let mut input = aoc::ProgramInput::with_args(PUZZLE, INPUT, vec![
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .possible_values(&["ascii", "dot", "json"])
+            .help("Render the tower in FORMAT"),
+        Arg::with_name("output")
+            .long("output")
+            .value_name("FILE")
+            .requires("format")
+            .help("Write the rendered tower to FILE instead of stdout"),
+    ]);
     println!("Day 7: {}", PUZZLE);
 
-    let iter = input.to_str().lines().map(|s| s.parse().unwrap());
-    let root = Node::from_iter(iter).unwrap();
+    let format: Option<Format> = input.value_of("format").map(|s| s.parse().unwrap());
+    let output = input.value_of("output").map(String::from);
+
+    let root = match NodeDefinition::parse_all(input.to_str()).and_then(|defs| Node::from_iter(defs.into_iter())) {
+        Ok(root) => root,
+        Err(e) => {
+            println!(":: Error: {}", e);
+            return;
+        }
+    };
     println!(":: Answer 1 is {}", root.name());
+    if input.verbosity() > 0 {
+        print!("{}", root.balance_report());
+    }
     match root.suggest_balance() {
-        Some(op) => println!(":: Answer 2 is {}", op),
-        None => println!(":: Answer 2 is none"),
+        Ok(Resolution::Fix(op)) => println!(":: Answer 2 is {}", op),
+        Ok(r) => println!(":: Answer 2 is {}", r),
+        Err(e) => println!(":: Answer 2 could not be calculated: {}", e),
+    }
+
+    if let Some(format) = format {
+        let rendered = root.render(format);
+        match output {
+            Some(path) => File::create(path)
+                .and_then(|mut f| f.write_all(rendered.as_bytes()))
+                .expect("cannot write rendered tower"),
+            None => print!("{}", rendered),
+        }
     }
 }
 
-#[derive(Debug)]
-pub struct Node {
+/// A program standing in the tower.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
     name: String,
     weight: u32,
-    children: Vec<Box<Node>>,
+
+    /// Weight of the program and everything it holds up.
+    ///
+    /// This is a cache filled in by `Node::from_iter`, the only place where
+    /// nodes are linked together; any other code that links nodes has to
+    /// call `update_totals` afterwards.
+    total: u32,
+
+    /// Whether every disc from this program up is balanced, cached
+    /// together with `total`.
+    balanced: bool,
 }
 
+/// A handle to a program in the tower, together with everything it holds up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node(tree::Node<Program>);
+
 impl Node {
     pub fn new(name: &str) -> Self {
-        Node{
-            name: String::from(name),
-            weight: 0,
-            children: Vec::new(),
-        }
+        Node::with_weight(name, 0)
     }
 
     pub fn with_weight(name: &str, weight: u32) -> Self {
-        let mut node = Node::new(name);
-        node.weight = weight;
-        node
+        Node(tree::Node::new(Program{
+            name: String::from(name),
+            weight,
+            total: weight,
+            balanced: true,
+        }))
     }
 
     /// Builds a `Node` from an iterator of `NodeDefinition`.
     ///
-    /// # Safety
-    ///
-    /// This function uses unsafe and might leak memory or perform a double-free.
-    /// It has not been extensively tested.
-    pub fn from_iter<'a, I>(it: I) -> Result<Box<Self>, ParseError>
+    /// The definitions may come in any order. They are first collected into
+    /// an arena, where the parent of every node is determined and the
+    /// structure is checked, before the nodes are linked together.
+    pub fn from_iter<I>(it: I) -> Result<Self, ParseError>
     where I: Iterator<Item = NodeDefinition> {
-        let mut nodes: HashMap<String, *mut Node> = HashMap::new();
-        let mut refs: HashMap<String, *mut Node> = HashMap::new();
-        let remove_node = |nodes: &mut HashMap<String, *mut Node>, refs: &mut HashMap<String, *mut Node>, key: &str| -> Option<*mut Node> {
-            nodes.remove(key)
-                .and_then(|x| { refs.insert(String::from(key), x); Some(x) })
-                .or_else(|| refs.get(key).map(|x| *x))
-        };
-        let cleanup_nodes = |nodes: HashMap<String, *mut Node>| {
-            nodes.values().for_each(|n: &*mut Node| unsafe { Box::from_raw(*n); });
-        };
+        let defs: Vec<NodeDefinition> = it.collect();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for (i, def) in defs.iter().enumerate() {
+            if index.insert(def.name.as_str(), i).is_some() {
+                return Err(ParseError::Duplicate{ name: def.name.clone() });
+            }
+        }
 
-        // Extract all nodes from the iterator and put them into the `nodes` hash map.
-        // If a node has children, we put the definition in the `edges` vector, because
-        // the referenced children might not exist yet in the `nodes` hash map.
-        let mut edges = Vec::new();
-        for def in it {
-            let ptr = Box::new(Node::with_weight(def.name.as_str(), def.weight));
-            let ptr = Box::into_raw(ptr);
-            nodes.insert(def.name.clone(), ptr);
-            if !def.children.is_empty() {
-                edges.push(def);
+        // Resolve the children of every node to indices into the arena,
+        // and make sure that every node has at most one parent.
+        let mut parents: Vec<Option<usize>> = vec![None; defs.len()];
+        let mut children: Vec<Vec<usize>> = Vec::with_capacity(defs.len());
+        for (i, def) in defs.iter().enumerate() {
+            let mut list = Vec::with_capacity(def.children.len());
+            for child in def.children.iter() {
+                let c = *index.get(child.as_str()).ok_or_else(|| ParseError::RefNotExist{
+                    from: def.name.clone(),
+                    to: child.clone(),
+                })?;
+                if let Some(p) = parents[c] {
+                    return Err(ParseError::MultipleParents{
+                        child: child.clone(),
+                        parents: vec![defs[p].name.clone(), def.name.clone()],
+                    });
+                }
+                parents[c] = Some(i);
+                list.push(c);
             }
+            children.push(list);
         }
 
-        // Link the nodes together by taking children and putting them in boxes.
-        // When we do this for a child, we have to remove it from the nodes hash map
-        // and put it in the refs hash map.
-        //
-        // At the end, there should be ONE node left in hash map, which will then
-        // be the root.
-        for def in edges {
-            // This can't fail, because we added the parents to the hash map:
-            let parent = nodes.get(def.name.as_str())
-                .or_else(|| refs.get(def.name.as_str()))
-                .map(|x| *x)
-                .unwrap();
-
-            for child in def.children {
-                match remove_node(&mut nodes, &mut refs, child.as_str()) {
-                    Some(node) => unsafe {
-                        (*parent).children.push(Box::from_raw(node));
-                    },
-                    None => {
-                        cleanup_nodes(nodes);
-                        return Err(ParseError::RefNotExist{
-                            from: def.name.clone(),
-                            to: String::from(child),
+        // Since every node has at most one parent, following the parents
+        // from any node either ends at a root or runs in a cycle.
+        let mut state = vec![Visit::New; defs.len()];
+        for i in 0..defs.len() {
+            let mut path: Vec<usize> = Vec::new();
+            let mut cur = Some(i);
+            while let Some(c) = cur {
+                match state[c] {
+                    Visit::Done => break,
+                    Visit::Active(start) if start == i => {
+                        let pos = path.iter().position(|x| *x == c).unwrap();
+                        return Err(ParseError::Cycle{
+                            nodes: path[pos..].iter().map(|x| defs[*x].name.clone()).collect(),
                         });
-                    },
+                    }
+                    _ => {
+                        state[c] = Visit::Active(i);
+                        path.push(c);
+                        cur = parents[c];
+                    }
                 }
             }
+            path.iter().for_each(|x| state[*x] = Visit::Done);
         }
 
-        // At this point, the `nodes` hash map should have only one entry.
-        // Otherwise, we have a tree that is not simply connected.
-        if nodes.len() != 1 {
-            let roots = nodes.keys().cloned().collect();
-            cleanup_nodes(nodes);
+        // Without cycles, there should be exactly one root.
+        let roots: Vec<usize> = (0..defs.len()).filter(|i| parents[*i].is_none()).collect();
+        if roots.len() != 1 {
             return Err(ParseError::NotConnected{
-                roots: roots,
+                roots: roots.iter().map(|x| defs[*x].name.clone()).collect(),
             });
         }
 
-        unsafe {
-            let root_ptr = nodes.drain().next().unwrap().1;
-            Ok(Box::from_raw(root_ptr))
+        // Link the tree in reverse pre-order, so that every node is still
+        // detached when its children are appended to it. That keeps the
+        // cycle check in `append` from walking up any ancestors.
+        let mut order = Vec::with_capacity(defs.len());
+        let mut stack = vec![roots[0]];
+        while let Some(i) = stack.pop() {
+            order.push(i);
+            stack.extend(children[i].iter().cloned());
         }
+        let mut arena: Vec<Node> = defs.iter()
+            .map(|def| Node::with_weight(def.name.as_str(), def.weight))
+            .collect();
+        for &i in order.iter().rev() {
+            for c in children[i].iter() {
+                let child = arena[*c].0.clone();
+                arena[i].0.append(child);
+            }
+        }
+
+        let root = arena.swap_remove(roots[0]);
+        root.update_totals()?;
+        Ok(root)
+    }
+
+    /// Recomputes the total weight of every node, and whether its tower
+    /// is balanced, in a single post-order pass, so that each child is
+    /// done before its parent.
+    fn update_totals(&self) -> Result<(), ParseError> {
+        for node in self.0.post_order() {
+            let weight = node.data().weight;
+            let total = node.children()
+                .try_fold(weight, |acc, c| acc.checked_add(c.data().total))
+                .ok_or_else(|| ParseError::Overflow{ name: node.data().name.clone() })?;
+            let first = node.children().next().map(|c| c.data().total);
+            let balanced = node.children().all(|c| c.data().balanced && Some(c.data().total) == first);
+            let mut data = node.data_mut();
+            data.total = total;
+            data.balanced = balanced;
+        }
+        Ok(())
     }
 
-    pub fn name(&self) -> &str { self.name.as_str() }
+    pub fn name(&self) -> Ref<'_, str> {
+        Ref::map(self.0.data(), |p| p.name.as_str())
+    }
 
     /// Returns the weight of the node.
     ///
@@ -226,69 +314,465 @@ impl Node {
     /// let node = Node::with_weight("a", 1);
     /// assert_eq!(node.weight(), 1);
     /// ```
-    pub fn weight(&self) -> u32 { self.weight }
+    pub fn weight(&self) -> u32 { self.0.data().weight }
 
-    pub fn get_balance(&self) -> u32 {
-        // TODO: Consider caching this or providing a cached variant.
-        self.children.iter().fold(self.weight(), |acc, x| acc + x.get_balance())
+    /// Returns the programs standing directly on the disc of this program.
+    pub fn children(&self) -> impl Iterator<Item = Node> {
+        self.0.children().map(Node)
     }
 
+    /// Returns the weight of the node and everything it holds up,
+    /// as computed when the tower was built.
+    pub fn get_balance(&self) -> u32 { self.0.data().total }
+
     pub fn is_balanced(&self) -> bool {
-        if self.children.is_empty() {
-            return true;
+        let mut iter = self.children();
+        match iter.next() {
+            Some(first) => {
+                let target = first.get_balance();
+                iter.all(|x| x.get_balance() == target)
+            }
+            None => true,
         }
-        let mut iter = self.children.iter();
-        let target = iter.next().unwrap().get_balance();
-        iter.all(|x| x.get_balance() == target)
     }
 
-    /// Returns a suggestion on how to balance the children.
-    pub fn suggest_balance(&self) -> Option<BalanceOp> {
-        // I can only make suggestions for children, not for myself.
-        if self.children.is_empty() {
-            return None
-        }
+    /// Returns true if every disc in the tower is balanced,
+    /// as computed when the tower was built.
+    pub fn is_tower_balanced(&self) -> bool { self.0.data().balanced }
 
-        self.children
-            .iter()
-            .find(|c| !c.is_balanced())
-            .and_then(|c| c.suggest_balance())
-            .or_else(|| self.get_unbalanced_child())
+    /// Returns a suggestion on how to balance the tower by changing
+    /// the weight of a single program.
+    pub fn suggest_balance(&self) -> Result<Resolution, BalanceError> {
+        self.resolve(None)
     }
 
-    /// Returns the unbalanced child, assuming it exists.
-    fn get_unbalanced_child(&self) -> Option<BalanceOp> {
-        let mut counts = HashMap::new();
-        self.children.iter().for_each(|x| {
-            let c = counts.entry(x.get_balance()).or_insert(0);
-            *c += 1;
-        });
-        counts.iter().max_by(|x, y| x.1.cmp(y.1)) // find the most common weight
-            .and_then(|target| { // find the child that does not equal target weight
-                self.children.iter()
-                    .find(|x| x.get_balance() != *target.0)
-                    .map(|c| {
-                        BalanceOp{
-                            node: c,
-                            weight_to: *target.0 - (c.get_balance() - c.weight()),
+    /// Finds the weight change that makes this tower balanced and, if
+    /// `expected` is given, makes its total weight equal to `expected`.
+    ///
+    /// The search descends one disc at a time with an explicit stack
+    /// rather than recursion, so that tall towers cannot overflow it.
+    fn resolve(&self, expected: Option<u32>) -> Result<Resolution, BalanceError> {
+        let mut frames: Vec<Candidates> = Vec::new();
+        let mut next = (self.clone(), expected);
+        loop {
+            let mut result = match next.0.step(next.1) {
+                Step::Descend(node, expected) => {
+                    next = (node, expected);
+                    continue;
+                }
+                Step::Branch(list, fallback) => {
+                    frames.push(Candidates{ list: list.into_iter(), ops: Vec::new(), error: None, fallback });
+                    None
+                }
+                Step::Done(r) => Some(r),
+            };
+
+            // Hand the result to the disc that asked for it, and move on to
+            // its next candidate, or finish it if there are none left.
+            loop {
+                let frame = match frames.last_mut() {
+                    Some(frame) => frame,
+                    None => return result.unwrap(),
+                };
+                if let Some(r) = result.take() {
+                    frame.record(r);
+                }
+                match frame.list.next() {
+                    Some((node, target)) => {
+                        next = (node, Some(target));
+                        break;
+                    }
+                    None => result = Some(frames.pop().unwrap().finish()),
+                }
+            }
+        }
+    }
+
+    /// Works out what to do about this disc alone: either the answer is
+    /// known, or it is that of a single sub-tower, or it has to be merged
+    /// from several candidate sub-towers.
+    fn step(&self, expected: Option<u32>) -> Step {
+        let children: Vec<(Node, u32)> = self.children().map(|c| { let w = c.get_balance(); (c, w) }).collect();
+        let mut counts: Vec<(u32, usize)> = Vec::new();
+        for &(_, w) in children.iter() {
+            match counts.iter_mut().find(|x| x.0 == w) {
+                Some(x) => x.1 += 1,
+                None => counts.push((w, 1)),
+            }
+        }
+
+        let candidates = match (counts.len(), expected) {
+            // All sub-towers weigh the same, so if anything is wrong with
+            // the total, then it has to be the weight of this program.
+            (0, _) | (1, _) => {
+                let total = self.get_balance();
+                let target = expected.unwrap_or(total);
+                let mut wrong = children.iter().filter(|c| !c.0.is_tower_balanced());
+                return match (wrong.next(), wrong.next()) {
+                    (None, _) if target == total => Step::Done(Ok(Resolution::Balanced)),
+                    (None, _) => Step::Done(target.checked_sub(total - self.weight())
+                        .map(|w| Resolution::Fix(BalanceOp{ node: self.clone(), weight_to: w }))
+                        .ok_or_else(|| BalanceError::NegativeWeight{
+                            name: self.name().to_string(),
+                            total: target,
+                        })),
+                    // The sub-tower has to keep its total, unless nothing
+                    // constrains it: no siblings and no expected total.
+                    (Some(c), None) if target == total => {
+                        if children.len() == 1 && expected.is_none() {
+                            Step::Descend(c.0.clone(), None)
+                        } else {
+                            Step::Descend(c.0.clone(), Some(c.1))
                         }
-                    })
-            })
+                    }
+                    _ => Step::Done(Err(self.unresolvable(&counts))),
+                };
+            }
+
+            // The expected total tells us what each sub-tower should weigh.
+            (2, Some(e)) => {
+                let n = children.len() as u32;
+                match e.checked_sub(self.weight()) {
+                    Some(x) if x % n == 0 && counts.iter().any(|c| c.0 == x / n) => vec![x / n],
+                    _ => return Step::Done(Err(self.unresolvable(&counts))),
+                }
+            }
+
+            // Otherwise the odd one out is wrong, or if there are only two
+            // sub-towers, we cannot know which one is wrong.
+            (2, None) => {
+                if counts[0].1 == 1 && counts[1].1 == 1 {
+                    vec![counts[1].0, counts[0].0]
+                } else if counts[0].1 == 1 {
+                    vec![counts[1].0]
+                } else if counts[1].1 == 1 {
+                    vec![counts[0].0]
+                } else {
+                    return Step::Done(Err(self.unresolvable(&counts)));
+                }
+            }
+
+            _ => return Step::Done(Err(self.unresolvable(&counts))),
+        };
+
+        // A single weight change only affects one sub-tower, so the
+        // others need to be balanced already for a candidate to work.
+        let mut list = Vec::new();
+        for target in candidates {
+            let mut wrong = children.iter().enumerate().filter(|&(_, c)| c.1 != target);
+            let (i, child) = match (wrong.next(), wrong.next()) {
+                (Some((i, c)), None) => (i, &c.0),
+                _ => return Step::Done(Err(self.unresolvable(&counts))),
+            };
+            if children.iter().enumerate().all(|(j, c)| j == i || c.0.is_tower_balanced()) {
+                list.push((child.clone(), target));
+            }
+        }
+        Step::Branch(list, self.unresolvable(&counts))
+    }
+
+    fn unresolvable(&self, counts: &[(u32, usize)]) -> BalanceError {
+        BalanceError::Unresolvable{
+            name: self.name().to_string(),
+            totals: counts.iter().map(|x| x.0).collect(),
+        }
     }
+
+    /// Returns a report of every disc in the tower whose sub-towers
+    /// do not all weigh the same, in depth-first pre-order.
+    pub fn balance_report(&self) -> BalanceReport {
+        BalanceReport {
+            discs: self.0.depth_first()
+                .map(Node)
+                .filter(|n| !n.is_balanced())
+                .map(|n| UnbalancedDisc {
+                    children: n.children().map(|c| { let w = c.get_balance(); (c, w) }).collect(),
+                    node: n,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A disc whose sub-towers do not all weigh the same.
+#[derive(Debug)]
+pub struct UnbalancedDisc {
+    node: Node,
+    children: Vec<(Node, u32)>,
 }
 
+impl UnbalancedDisc {
+    pub fn node(&self) -> &Node { &self.node }
+
+    /// Returns the programs on the disc together with their total weights.
+    pub fn children(&self) -> &[(Node, u32)] { &self.children }
+}
+
+/// All unbalanced discs of a tower, in depth-first pre-order: starting
+/// from the bottom program, every disc comes before the discs standing
+/// on it, and sub-towers are visited one after the other.
 #[derive(Debug)]
-pub struct BalanceOp<'a> {
-    node: &'a Node,
+pub struct BalanceReport {
+    discs: Vec<UnbalancedDisc>,
+}
+
+impl BalanceReport {
+    pub fn discs(&self) -> &[UnbalancedDisc] { &self.discs }
+
+    pub fn is_balanced(&self) -> bool { self.discs.is_empty() }
+}
+
+impl fmt::Display for BalanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for d in self.discs.iter() {
+            write!(f, "disc of {} ({}) is unbalanced:", d.node.name(), d.node.weight())?;
+            for &(ref c, w) in d.children.iter() {
+                write!(f, " {}={}", c.name(), w)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct BalanceOp {
+    node: Node,
     weight_to: u32,
 }
 
-impl<'a> fmt::Display for BalanceOp<'a> {
+impl fmt::Display for BalanceOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "balance {} by modifying weight {} -> {}", self.node.name(), self.node.weight(), self.weight_to)
+    }
+}
+
+/// What `Node::resolve` has to do next about a single disc.
+enum Step {
+    /// The answer for the disc is known.
+    Done(Result<Resolution, BalanceError>),
+
+    /// The answer is that of this sub-tower, given the weight it should have.
+    Descend(Node, Option<u32>),
+
+    /// The answer is merged from those of these sub-towers, each with the
+    /// weight it should have, or is the given error if none of them helps.
+    Branch(Vec<(Node, u32)>, BalanceError),
+}
+
+/// A disc in `Node::resolve` waiting for the answers of its candidates.
+struct Candidates {
+    list: vec::IntoIter<(Node, u32)>,
+    ops: Vec<BalanceOp>,
+    error: Option<BalanceError>,
+    fallback: BalanceError,
+}
+
+impl Candidates {
+    fn record(&mut self, result: Result<Resolution, BalanceError>) {
+        match result {
+            Ok(Resolution::Fix(op)) => self.ops.push(op),
+            Ok(Resolution::Ambiguous(list)) => self.ops.extend(list),
+            Ok(Resolution::Balanced) => {}
+            Err(e) => self.error = self.error.take().or(Some(e)),
+        }
+    }
+
+    fn finish(mut self) -> Result<Resolution, BalanceError> {
+        match self.ops.len() {
+            0 => Err(self.error.unwrap_or(self.fallback)),
+            1 => Ok(Resolution::Fix(self.ops.pop().unwrap())),
+            _ => Ok(Resolution::Ambiguous(self.ops)),
+        }
+    }
+}
+
+/// The result of trying to balance a tower.
+#[derive(Debug)]
+pub enum Resolution {
+    /// The tower is already balanced.
+    Balanced,
+
+    /// The tower is balanced by applying this operation.
+    Fix(BalanceOp),
+
+    /// The tower is balanced by applying any one of these operations.
+    Ambiguous(Vec<BalanceOp>),
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Resolution::Balanced => write!(f, "tower is balanced"),
+            Resolution::Fix(ref op) => write!(f, "{}", op),
+            Resolution::Ambiguous(ref ops) => {
+                write!(f, "ambiguous, either")?;
+                for (i, op) in ops.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { "" } else { " or" }, op)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BalanceError {
+    /// Error due to a program that would need a negative weight.
+    NegativeWeight{
+        name: String,
+        total: u32,
+    },
+
+    /// Error due to a disc that cannot be balanced by changing a single weight.
+    Unresolvable{
+        name: String,
+        totals: Vec<u32>,
+    },
+}
+
+impl error::Error for BalanceError {
+    fn description(&self) -> &str {
+        match *self {
+            BalanceError::NegativeWeight{..} => "program would need a negative weight",
+            BalanceError::Unresolvable{..} => "disc cannot be balanced by changing a single weight",
+        }
+    }
+}
+
+impl fmt::Display for BalanceError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "balance {} by modifying weight {} -> {}", self.node.name, self.node.weight, self.weight_to)
+        match *self {
+            BalanceError::NegativeWeight{ ref name, total } => write!(f, "program {} would need a negative weight to weigh {} in total", name, total),
+            BalanceError::Unresolvable{ ref name, ref totals } => write!(f, "disc of {} cannot be balanced by changing a single weight: {:?}", name, totals),
+        }
+    }
+}
+
+/// The formats a tower can be rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// An indented tree, annotated with own and total weight.
+    Ascii,
+
+    /// A Graphviz digraph, with unbalanced discs highlighted.
+    Dot,
+
+    /// Nested JSON objects.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Format::Ascii),
+            "dot" => Ok(Format::Dot),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+impl Node {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Ascii => self.to_ascii(),
+            Format::Dot => self.to_dot(),
+            Format::Json => self.to_json(),
+        }
+    }
+
+    /// Renders the tower as an indented tree, where every program is
+    /// annotated with its own weight and its total weight:
+    ///
+    /// ```text
+    /// tknk (41, 778)
+    /// +-- ugml (68, 251)
+    /// |   +-- gyxo (61, 61)
+    /// |   `-- jptl (61, 61)
+    /// `-- padx (45, 243)
+    /// ```
+    pub fn to_ascii(&self) -> String {
+        let mut out = String::new();
+        let mut stack = vec![(self.clone(), String::new(), String::new())];
+        while let Some((node, lead, indent)) = stack.pop() {
+            out.push_str(&format!("{}{} ({}, {})\n", lead, node.name(), node.weight(), node.get_balance()));
+            let children: Vec<Node> = node.children().collect();
+            let n = children.len();
+            for (i, c) in children.into_iter().enumerate().rev() {
+                let last = i + 1 == n;
+                let lead = format!("{}{}", indent, if last { "`-- " } else { "+-- " });
+                let next = format!("{}{}", indent, if last { "    " } else { "|   " });
+                stack.push((c, lead, next));
+            }
+        }
+        out
+    }
+
+    /// Renders the tower as a Graphviz digraph, in which the programs
+    /// holding an unbalanced disc are filled red.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph tower {\n    node [shape=box];\n");
+        for node in self.0.depth_first().map(Node) {
+            let name = escape(&node.name());
+            out.push_str(&format!("    \"{}\" [label=\"{}\\n{} / {}\"", name, name, node.weight(), node.get_balance()));
+            if !node.is_balanced() {
+                out.push_str(", style=filled, fillcolor=red");
+            }
+            out.push_str("];\n");
+            for c in node.children() {
+                out.push_str(&format!("    \"{}\" -> \"{}\";\n", name, escape(&c.name())));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the tower as nested JSON objects of the form:
+    ///
+    /// ```text
+    /// {"name":"a","weight":1,"total":3,"balanced":true,"children":[...]}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut out = self.0.fold(|p, children: Vec<(String, u32)>| {
+            let balanced = children.windows(2).all(|w| w[0].1 == w[1].1);
+            let list: Vec<String> = children.into_iter().map(|c| c.0).collect();
+            let json = format!(
+                "{{\"name\":\"{}\",\"weight\":{},\"total\":{},\"balanced\":{},\"children\":[{}]}}",
+                escape(&p.name), p.weight, p.total, balanced, list.join(",")
+            );
+            (json, p.total)
+        }).0;
+        out.push('\n');
+        out
     }
 }
 
+/// Escapes a string for use inside double quotes in DOT and JSON.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The state of a node while looking for cycles in `Node::from_iter`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    Active(usize),
+    Done,
+}
+
 /// A temporary result used to create a new Node from a string slice.
 #[derive(Debug, PartialEq, Eq)]
 pub struct NodeDefinition {
@@ -297,6 +781,31 @@ pub struct NodeDefinition {
     children: Vec<String>,
 }
 
+impl NodeDefinition {
+    /// Parses one definition per line, skipping lines that are empty
+    /// or contain only a comment.
+    pub fn parse_all(s: &str) -> Result<Vec<Self>, ParseError> {
+        let mut defs = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let content = match line.find('#') {
+                Some(n) => &line[..n],
+                None => line,
+            };
+            if content.trim().is_empty() {
+                continue;
+            }
+            match line.parse() {
+                Ok(def) => defs.push(def),
+                Err(ParseError::Malformed{ column, msg, data, .. }) => {
+                    return Err(ParseError::Malformed{ line: i + 1, column, msg, data });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(defs)
+    }
+}
+
 impl FromStr for NodeDefinition {
     type Err = ParseError;
 
@@ -308,25 +817,126 @@ impl FromStr for NodeDefinition {
     /// pbga (66)
     /// fwft (72) -> ktlj, cntj, xhth
     /// ```
+    ///
+    /// Names consist of alphanumeric characters and underscores, any amount
+    /// of whitespace may surround the tokens, and a `#` starts a comment
+    /// that runs to the end of the line.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(
-                r"^(?P<name>[[:alpha:]]+) \((?P<weight>\d+)\)(?: -> (?P<children>(?:[[:alpha:]]+(?:, )?)+))?$"
-            ).unwrap();
-        }
+        let mut cur = Cursor::new(s);
+        cur.skip_whitespace();
+        let name = cur.name()?;
+        cur.skip_whitespace();
+        cur.expect('(', "expecting '(' before weight")?;
+        cur.skip_whitespace();
+        let weight = cur.weight()?;
+        cur.skip_whitespace();
+        cur.expect(')', "expecting ')' after weight")?;
+        cur.skip_whitespace();
 
-        RE.captures(s)
-            .map(|captures| {
-            Self{
-                name: String::from(captures.name("name").unwrap().as_str()),
-                weight: captures.name("weight").unwrap().as_str().parse().unwrap(),
-                children: match captures.name("children") {
-                            Some(children) => children.as_str().split(", ").map(|x| String::from(x)).collect(),
-                            None => Vec::new(),
-                        },
+        let mut children = Vec::new();
+        if cur.peek() == Some('-') {
+            cur.bump();
+            cur.expect('>', "expecting '->' before children")?;
+            loop {
+                cur.skip_whitespace();
+                children.push(cur.name()?);
+                cur.skip_whitespace();
+                if cur.peek() != Some(',') {
+                    break;
+                }
+                cur.bump();
             }
-            })
-            .ok_or(ParseError::Malformed{ line: String::from(s) })
+        }
+
+        match cur.peek() {
+            None | Some('#') => Ok(NodeDefinition{ name, weight, children }),
+            Some(_) => Err(cur.error("unexpected character, expecting '->', ',', or end of line")),
+        }
+    }
+}
+
+impl fmt::Display for NodeDefinition {
+    /// Formats the definition in the canonical form that `from_str` expects.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.weight)?;
+        if !self.children.is_empty() {
+            write!(f, " -> {}", self.children.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A position in a single line that is being parsed.
+struct Cursor<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a str) -> Self {
+        Cursor { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.data[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn expect(&mut self, c: char, msg: &'static str) -> Result<(), ParseError> {
+        if self.peek() == Some(c) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(msg))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+        if name.is_empty() {
+            Err(self.error("expecting a name"))
+        } else {
+            Ok(String::from(name))
+        }
+    }
+
+    fn weight(&mut self) -> Result<u32, ParseError> {
+        let start = self.pos;
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return Err(self.error("expecting a weight"));
+        }
+        digits.parse().map_err(|_| {
+            self.pos = start;
+            self.error("weight is too large")
+        })
+    }
+
+    /// Returns an error at the current position, with a 1-based column.
+    fn error(&self, msg: &'static str) -> ParseError {
+        ParseError::Malformed{
+            line: 1,
+            column: self.data[..self.pos].chars().count() + 1,
+            msg,
+            data: String::from(self.data),
+        }
     }
 }
 
@@ -338,15 +948,39 @@ pub enum ParseError {
         to: String,
     },
 
-    /// Error due to a malformed node definition.
+    /// Error due to a malformed node definition, at a 1-based line and column.
     Malformed{
-        line: String,
+        line: usize,
+        column: usize,
+        msg: &'static str,
+        data: String,
     },
 
     /// Error due to tree not simply connected, i.e., has multiple roots.
     NotConnected{
         roots: Vec<String>,
-    }
+    },
+
+    /// Error due to a node being listed as the child of more than one node.
+    MultipleParents{
+        child: String,
+        parents: Vec<String>,
+    },
+
+    /// Error due to nodes that are (indirectly) their own parents.
+    Cycle{
+        nodes: Vec<String>,
+    },
+
+    /// Error due to a node being defined more than once.
+    Duplicate{
+        name: String,
+    },
+
+    /// Error due to the total weight of a tower not fitting into `u32`.
+    Overflow{
+        name: String,
+    },
 }
 
 impl error::Error for ParseError {
@@ -355,6 +989,10 @@ impl error::Error for ParseError {
             ParseError::RefNotExist{..} => "reference to node does not exist",
             ParseError::Malformed{..} => "cannot parse line into node",
             ParseError::NotConnected{..} => "tree is not simply connected",
+            ParseError::MultipleParents{..} => "node has more than one parent",
+            ParseError::Cycle{..} => "tree contains a cycle",
+            ParseError::Duplicate{..} => "node is defined more than once",
+            ParseError::Overflow{..} => "total weight of tower is too large",
         }
     }
 }
@@ -363,8 +1001,12 @@ impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ParseError::RefNotExist{ ref from, ref to } => write!(f, "reference from {} to {} does not exist", from, to),
-            ParseError::Malformed{ ref line } => write!(f, "cannot parse line: {}", line),
+            ParseError::Malformed{ line, column, msg, ref data } => write!(f, "{}: at line {}, column {} in: {}", msg, line, column, data),
             ParseError::NotConnected{ ref roots } => write!(f, "tree is not simply connected: {:?}", roots),
+            ParseError::MultipleParents{ ref child, ref parents } => write!(f, "node {} has more than one parent: {:?}", child, parents),
+            ParseError::Cycle{ ref nodes } => write!(f, "tree contains a cycle: {:?}", nodes),
+            ParseError::Duplicate{ ref name } => write!(f, "node {} is defined more than once", name),
+            ParseError::Overflow{ ref name } => write!(f, "total weight of tower at {} is too large", name),
         }
     }
 }
@@ -391,6 +1033,249 @@ mod tests {
             assert_eq!(t.0.parse::<NodeDefinition>().unwrap(), t.1);
         }
     }
+
+    #[test]
+    fn test_parse_relaxed() {
+        let tests = vec![
+            ("a_1 (0)", "a_1 (0)"),
+            ("  pbga(66)  ", "pbga (66)"),
+            ("fwft ( 72 )->ktlj,cntj ,  xhth", "fwft (72) -> ktlj, cntj, xhth"),
+            ("fwft (72) -> ktlj, xhth # comment", "fwft (72) -> ktlj, xhth"),
+            ("x9 (4294967295)#", "x9 (4294967295)"),
+        ];
+
+        for t in tests {
+            let def: NodeDefinition = t.0.parse().unwrap();
+            assert_eq!(def.to_string(), t.1);
+            assert_eq!(def.to_string().parse::<NodeDefinition>().unwrap(), def);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let tests = vec![
+            ("", 1, "expecting a name"),
+            ("pbga 66", 6, "expecting '(' before weight"),
+            ("pbga ()", 7, "expecting a weight"),
+            ("pbga (-1)", 7, "expecting a weight"),
+            ("pbga (4294967296)", 7, "weight is too large"),
+            ("pbga (66", 9, "expecting ')' after weight"),
+            ("pbga (66) >", 11, "unexpected character, expecting '->', ',', or end of line"),
+            ("pbga (66) -> a,", 16, "expecting a name"),
+            ("pbga (66) -> a b", 16, "unexpected character, expecting '->', ',', or end of line"),
+            ("pbga-x (66)", 5, "expecting '(' before weight"),
+        ];
+
+        for t in tests {
+            match t.0.parse::<NodeDefinition>() {
+                Err(ParseError::Malformed{ column, msg, .. }) => assert_eq!((column, msg), (t.1, t.2), "input = {:?}", t.0),
+                r => panic!("unexpected result for {:?}: {:?}", t.0, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let defs = NodeDefinition::parse_all("# tower\n\na (1) -> b\n  # indented comment\nb (2)\n").unwrap();
+        assert_eq!(defs.len(), 2);
+        assert_eq!(defs.iter().map(|d| d.to_string()).collect::<Vec<_>>(), vec!["a (1) -> b", "b (2)"]);
+
+        match NodeDefinition::parse_all("a (1) -> b\n\nb (x)") {
+            Err(ParseError::Malformed{ line, column, .. }) => assert_eq!((line, column), (3, 4)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let defs = NodeDefinition::parse_all(EXAMPLE).unwrap();
+        let printed: Vec<String> = defs.iter().map(|d| d.to_string()).collect();
+        assert_eq!(printed.join("\n"), EXAMPLE);
+    }
+
+    const EXAMPLE: &str = "pbga (66)
+xhth (57)
+ebii (61)
+havc (66)
+ktlj (57)
+fwft (72) -> ktlj, cntj, xhth
+qoyq (66)
+padx (45) -> pbga, havc, qoyq
+tknk (41) -> ugml, padx, fwft
+jptl (61)
+ugml (68) -> gyxo, ebii, jptl
+gyxo (61)
+cntj (57)";
+
+    fn build(s: &str) -> Result<Node, ParseError> {
+        Node::from_iter(s.lines().map(|x| x.parse().unwrap()))
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let root = build(EXAMPLE).unwrap();
+        assert_eq!(&*root.name(), "tknk");
+        assert_eq!(root.get_balance(), 778);
+        let names: Vec<String> = root.children().map(|c| c.name().to_string()).collect();
+        assert_eq!(names, vec!["ugml", "padx", "fwft"]);
+        match root.suggest_balance() {
+            Ok(Resolution::Fix(op)) => assert_eq!((&*op.node.name(), op.weight_to), ("ugml", 60)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_suggest_balance() {
+        fn fixes(r: &Resolution) -> Vec<(String, u32)> {
+            match *r {
+                Resolution::Balanced => vec![],
+                Resolution::Fix(ref op) => vec![(op.node.name().to_string(), op.weight_to)],
+                Resolution::Ambiguous(ref ops) => ops.iter().map(|op| (op.node.name().to_string(), op.weight_to)).collect(),
+            }
+        }
+
+        let tests = vec![
+            ("a (1) -> b, c\nb (3)\nc (3)", vec![]),
+            ("a (1) -> b, c, d\nb (3)\nc (3)\nd (4)", vec![("d", 3)]),
+            ("a (1) -> b, c\nb (3)\nc (5)", vec![("b", 5), ("c", 3)]),
+            ("a (1) -> b\nb (3) -> c, d\nc (3)\nd (5)", vec![("c", 5), ("d", 3)]),
+            // The siblings of x tell us which of its children is wrong.
+            ("r (1) -> x, y, z\ny (10)\nz (10)\nx (2) -> p, q\np (3)\nq (4)", vec![("p", 4)]),
+            // Only the sub-tower of b can be fixed, since c is balanced.
+            ("a (1) -> b, c\nb (1) -> d, e\nd (1)\ne (2)\nc (5)", vec![("d", 2)]),
+        ];
+
+        for t in tests {
+            let root = build(t.0).unwrap();
+            let r = root.suggest_balance().unwrap();
+            let expect: Vec<(String, u32)> = t.1.iter().map(|x| (x.0.to_string(), x.1)).collect();
+            assert_eq!(fixes(&r), expect, "input = {:?}", t.0);
+        }
+    }
+
+    #[test]
+    fn test_suggest_balance_errors() {
+        let root = build("r (1) -> a, b, c\na (1)\nb (1)\nc (0) -> d\nd (5)").unwrap();
+        match root.suggest_balance() {
+            Err(BalanceError::NegativeWeight{ name, total }) => assert_eq!((name.as_str(), total), ("c", 1)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let tests = vec![
+            "r (1) -> a, b, c\na (1)\nb (2)\nc (3)",
+            "r (1) -> a, b, c, d\na (1)\nb (1)\nc (2)\nd (2)",
+            "r (1) -> x, y, z\ny (10)\nz (10)\nx (2) -> p, q\np (3)\nq (5)",
+        ];
+        for t in tests {
+            match build(t).unwrap().suggest_balance() {
+                Err(BalanceError::Unresolvable{..}) => {}
+                r => panic!("unexpected result for {:?}: {:?}", t, r),
+            }
+        }
+
+        match build("a (4294967295) -> b\nb (1)") {
+            Err(ParseError::Overflow{ name }) => assert_eq!(name, "a"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let root = build("a (1) -> b, c\nb (2) -> d\nd (3)\nc (4)").unwrap();
+        assert_eq!(root.render(Format::Ascii), "a (1, 10)\n+-- b (2, 5)\n|   `-- d (3, 3)\n`-- c (4, 4)\n");
+        assert_eq!(root.render(Format::Dot), "digraph tower {
+    node [shape=box];
+    \"a\" [label=\"a\\n1 / 10\", style=filled, fillcolor=red];
+    \"a\" -> \"b\";
+    \"a\" -> \"c\";
+    \"b\" [label=\"b\\n2 / 5\"];
+    \"b\" -> \"d\";
+    \"d\" [label=\"d\\n3 / 3\"];
+    \"c\" [label=\"c\\n4 / 4\"];
+}
+");
+        assert_eq!(root.render(Format::Json), concat!(
+            r#"{"name":"a","weight":1,"total":10,"balanced":false,"children":["#,
+            r#"{"name":"b","weight":2,"total":5,"balanced":true,"children":["#,
+            r#"{"name":"d","weight":3,"total":3,"balanced":true,"children":[]}]},"#,
+            r#"{"name":"c","weight":4,"total":4,"balanced":true,"children":[]}]}"#,
+            "\n"
+        ));
+        assert_eq!(escape("a\"b\\c\n"), r#"a\"b\\c\n"#);
+    }
+
+    #[test]
+    fn test_balance_report() {
+        let root = build(EXAMPLE).unwrap();
+        let report = root.balance_report();
+        assert_eq!(report.discs().len(), 1);
+        let totals: Vec<u32> = report.discs()[0].children().iter().map(|x| x.1).collect();
+        assert_eq!(totals, vec![251, 243, 243]);
+
+        // Two wrong weights in separate sub-towers.
+        let root = build("a (1) -> b, c, d\nb (1) -> e, f\ne (2)\nf (3)\nc (6)\nd (6) -> g, h\ng (1)\nh (2)").unwrap();
+        let report = root.balance_report();
+        let names: Vec<String> = report.discs().iter().map(|d| d.node().name().to_string()).collect();
+        assert_eq!(names, vec!["a", "b", "d"]);
+        assert_eq!(format!("{}", report), "disc of a (1) is unbalanced: b=6 c=6 d=9\n\
+            disc of b (1) is unbalanced: e=2 f=3\n\
+            disc of d (6) is unbalanced: g=1 h=2\n");
+    }
+
+    #[test]
+    fn test_deep() {
+        // A chain listed from the bottom program up must be built, and
+        // balanced, in linear time and without recursion.
+        let n = 100_000;
+        let chain = |top: &str| -> String {
+            let mut s: String = (0..n - 1).map(|i| format!("p{} (1) -> p{}\n", i, i + 1)).collect();
+            s.push_str(&format!("p{} {}", n - 1, top));
+            s
+        };
+        let root = build(&chain("(1)")).unwrap();
+        assert_eq!(&*root.name(), "p0");
+        assert_eq!(root.get_balance(), n);
+        assert!(root.is_tower_balanced());
+
+        // The same chain, holding up a disc with one wrong weight.
+        let root = build(&chain("(1) -> x, y, z\nx (1)\ny (1)\nz (2)")).unwrap();
+        assert!(!root.is_tower_balanced());
+        match root.suggest_balance() {
+            Ok(Resolution::Fix(op)) => assert_eq!((&*op.node.name(), op.weight_to), ("z", 1)),
+            r => panic!("unexpected resolution: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_from_iter_errors() {
+        match build("a (1) -> b\nb (1)\nc (1) -> b") {
+            Err(ParseError::MultipleParents{ child, parents }) => {
+                assert_eq!(child, "b");
+                assert_eq!(parents, vec!["a", "c"]);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        match build("r (1)\na (1) -> b\nb (1) -> c\nc (1) -> a") {
+            Err(ParseError::Cycle{ mut nodes }) => {
+                nodes.sort();
+                assert_eq!(nodes, vec!["a", "b", "c"]);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        match build("a (1) -> b, c\nb (1)") {
+            Err(ParseError::RefNotExist{ from, to }) => assert_eq!((from.as_str(), to.as_str()), ("a", "c")),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        match build("a (1) -> b\nb (1)\nc (1)") {
+            Err(ParseError::NotConnected{ roots }) => assert_eq!(roots, vec!["a", "c"]),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        match build("a (1)\na (2)") {
+            Err(ParseError::Duplicate{ name }) => assert_eq!(name, "a"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
 }
 
 const PUZZLE: &'static str = "Recursive Circus";
@@ -40,160 +40,662 @@ evaluated).
 */
 
 extern crate aoc;
+extern crate clap;
+extern crate num_bigint;
 
+use clap::Arg;
+use num_bigint::{BigInt, Sign};
 use std::fmt;
 use std::error;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::str::FromStr;
 
 fn main() {
-    let mut input = aoc::ProgramInput::new(PUZZLE, INPUT);
+    let mut input = aoc::ProgramInput::with_args(PUZZLE, INPUT, vec![
+        Arg::with_name("csv")
+            .long("csv")
+            .value_name("FILE")
+            .help("Write the register values after every statement as CSV to FILE"),
+        Arg::with_name("overflow")
+            .long("overflow")
+            .value_name("POLICY")
+            .possible_values(&["checked", "wrapping", "saturating", "unbounded"])
+            .default_value("checked")
+            .help("How to handle register values that do not fit into 64 bits"),
+    ]);
     println!("Day 8: {}", PUZZLE);
 
+    let verbose = input.verbosity() > 0;
+    let csv = input.value_of("csv").map(String::from);
+    let overflow: Overflow = input.value_of("overflow").unwrap().parse().unwrap();
     let prog: Program = input.to_str().lines().map(|s| s.parse().unwrap()).collect();
-    let mut reg = Register::new();
-    let alltime_max = prog.iter().fold(("_", 0), |max, x| {
-        match x.apply(&mut reg) {
-            Some(n) => if n > max.1 { (x.operation.left.ident(), n) } else { max },
-            None => max,
-        }
-    });
-    let current_max = reg.iter().max_by(|x,y| x.1.cmp(y.1));
+    let analysis = Analysis::new(&prog);
+    for w in analysis.warnings() {
+        println!("warning: {}", w);
+    }
+    if verbose {
+        let names: Vec<&str> = analysis.registers().iter().map(|s| s.as_str()).collect();
+        println!("-> {} registers: {}", names.len(), names.join(", "));
+    }
+
+    let mut exec = Executor::with_overflow(overflow);
+    if let Err(e) = exec.run(&prog) {
+        println!(":: Error: {}", e);
+        return;
+    }
+    if verbose {
+        for (name, h) in exec.histories() {
+            println!("-> {}: {} writes, min {}, max {}", name, h.values().len(), h.min(), h.max());
+        }
+    }
+    if let Some(path) = csv {
+        let mut w = BufWriter::new(File::create(path).expect("cannot create csv file"));
+        exec.write_csv(&mut w).expect("cannot write csv file");
+    }
+
+    let current_max = exec.registers().iter().max_by(|x,y| x.1.cmp(y.1));
     match current_max {
         Some(max) => println!(":: Answer 1 is {}={}", max.0, max.1),
         None => println!(":: No elements found"),
     }
-    println!(":: Answer 2 is {}={}", alltime_max.0, alltime_max.1);
+    match exec.max() {
+        Some((name, max)) => println!(":: Answer 2 is {}={}", name, max.value),
+        // Registers start at 0, so that is the maximum if nothing exceeded it.
+        None => println!(":: Answer 2 is _=0"),
+    }
 }
 
 type Program = Vec<Statement>;
 
-type Register = HashMap<String, isize>;
+type Register = HashMap<String, BigInt>;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Statement {
-    condition: BinaryOperation,
-    operation: BinaryOperation,
+    target: String,
+    op: Operator,
+    value: Expr,
+    condition: Expr,
 }
 
 impl Statement {
-    pub fn apply(&self, reg: &mut Register) -> Option<isize> {
-        if self.condition.apply(reg).as_bool() {
-            Some(self.operation.apply(reg).as_num())
-        } else {
-            None
+    /// Returns the name of the register that is modified.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the registers read by the statement, in evaluation order:
+    /// first those of the condition, then those of the value, then the
+    /// target itself unless the operator is `set`, which overwrites it.
+    pub fn reads(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.condition.registers(&mut names);
+        self.value.registers(&mut names);
+        if self.op != Operator::Set {
+            names.push(&self.target);
         }
+        names
+    }
+
+    /// Modifies the target register if the condition holds, and returns its new value.
+    /// Registers that have not been written yet are read as 0.
+    pub fn apply(&self, reg: &mut Register, overflow: Overflow) -> Result<Option<BigInt>, EvalError> {
+        if !self.condition.eval(reg, overflow)?.as_bool()? {
+            return Ok(None);
+        }
+        let n = self.value.resolve(reg, overflow)?;
+        let next = match reg.get(&self.target) {
+            Some(current) => self.op.compute(current, &n, overflow)?,
+            None => self.op.compute(&BigInt::from(0), &n, overflow)?,
+        };
+        reg.insert(self.target.clone(), next.clone());
+        Ok(Some(next))
     }
 }
 
 impl FromStr for Statement {
     type Err = ParseError;
 
-    /// # Safety
+    /// Parses a statement of the form:
     ///
-    /// This function assumes that the string comes in this format:
     /// ```text
-    /// c dec -10 if a >= 1
+    /// {ident} {op} {expr} if {expr}
     /// ```
-    /// That is,
-    /// ```
-    /// {ident} {op} {operand} if {operand} {op} {operand}
+    ///
+    /// where `op` is one of `inc`, `dec`, `set`, `mul`, `div`, or `mod`.
+    /// Expressions consist of numbers, registers, `true` and `false`,
+    /// the arithmetic operators `+ - * / %`, the comparisons
+    /// `== != < > <= >=`, the logical operators `and`, `or`, and `not`,
+    /// and parentheses. For example:
+    ///
+    /// ```text
+    /// c dec -10 if a >= 1
+    /// x set (a + b) * 2 if not (a == b or c < 0)
     /// ```
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens: Vec<String> = s.split_whitespace().map(|s| String::from(s)).collect();
-        if tokens.len() != 7 || tokens[3] != "if" {
-            return Err(ParseError::with_msg(s, "length is not 7 or token 3 is not if"));
-        }
-
-        // TODO: Put this all in one statement?
-        let target_op = BinaryOperation{
-            op: tokens[1].parse()?,
-            left: {
-                let ident: Operand = tokens[0].parse()?;
-                if !ident.is_ident() {
-                    return Err(ParseError::with_msg(s, "left operand is not an identifier"));
-                }
-                ident
-            },
-            right: tokens[2].parse()?,
-        };
-        let condition = BinaryOperation{
-            op: tokens[5].parse()?,
-            left: tokens[4].parse()?,
-            right: tokens[6].parse()?,
-        };
-        Ok(Statement{
-            operation: target_op,
-            condition: condition,
-        })
+        Parser::new(s)?.statement()
     }
 }
 
-#[derive(Debug)]
-pub struct BinaryOperation {
-    op: Operator,
-    left: Operand,
-    right: Operand,
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} if {}", self.target, self.op, self.value, self.condition)
+    }
 }
 
-impl BinaryOperation {
-    pub fn apply(&self, reg: &mut Register) -> Constant {
-        match self.op {
-            Operator::Eq => Constant::Bool(self.eq(reg)),
-            Operator::Ne => Constant::Bool(self.ne(reg)),
-            Operator::Le => Constant::Bool(self.le(reg)),
-            Operator::Ge => Constant::Bool(self.ge(reg)),
-            Operator::Lt => Constant::Bool(self.lt(reg)),
-            Operator::Gt => Constant::Bool(self.gt(reg)),
-            Operator::Inc => Constant::Num(self.inc(reg)),
-            Operator::Dec => Constant::Num(self.dec(reg)),
+/// Runs statements one by one, and records what happened along the way.
+#[derive(Debug, Default)]
+pub struct Executor {
+    reg: Register,
+    overflow: Overflow,
+    histories: BTreeMap<String, History>,
+    trace: Vec<Step>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_overflow(overflow: Overflow) -> Self {
+        Executor {
+            overflow,
+            ..Self::default()
         }
     }
 
-    fn eq(&self, reg: &mut Register) -> bool {
-        self.left.resolve(reg) == self.right.resolve(reg)
+    /// Executes a single statement, and returns the new value of
+    /// the target register if the condition held.
+    pub fn execute(&mut self, stmt: &Statement) -> Result<Option<BigInt>, RunError> {
+        let index = self.trace.len();
+        let result = stmt.apply(&mut self.reg, self.overflow)
+            .map_err(|cause| RunError{ index, cause })?;
+        for name in stmt.reads().into_iter().chain(Some(stmt.target())) {
+            self.reg.entry(String::from(name)).or_insert_with(|| BigInt::from(0));
+        }
+        if let Some(ref n) = result {
+            self.histories.entry(String::from(stmt.target()))
+                .or_insert_with(|| History::new(index, n.clone()))
+                .push(index, n.clone());
+        }
+        self.trace.push(Step {
+            target: String::from(stmt.target()),
+            condition: result.is_some(),
+            value: self.reg[stmt.target()].clone(),
+        });
+        Ok(result)
+    }
+
+    /// Executes all statements, stopping at the first one that fails.
+    pub fn run(&mut self, prog: &[Statement]) -> Result<(), RunError> {
+        for x in prog {
+            self.execute(x)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the current value of all registers that have been used,
+    /// including those that were only read.
+    pub fn registers(&self) -> &Register {
+        &self.reg
+    }
+
+    /// Returns the history of a register, or `None` if it was never written.
+    pub fn history(&self, name: &str) -> Option<&History> {
+        self.histories.get(name)
+    }
+
+    /// Returns the history of all written registers, sorted by name.
+    pub fn histories(&self) -> &BTreeMap<String, History> {
+        &self.histories
+    }
+
+    /// Returns one step for each executed statement.
+    pub fn trace(&self) -> &[Step] {
+        &self.trace
     }
-    fn ne(&self, reg: &mut Register) -> bool {
-        !self.eq(reg)
+
+    /// Returns the highest value any register held at any time, or `None`
+    /// if no register ever went above 0, the value they all start with.
+    /// If there are several, the earliest one wins.
+    pub fn max(&self) -> Option<(&str, &Extreme)> {
+        self.histories.iter()
+            .map(|(name, h)| (name.as_str(), h.max()))
+            .filter(|x| x.1.value.sign() == Sign::Plus)
+            .max_by(|x, y| x.1.value.cmp(&y.1.value).then(y.1.index.cmp(&x.1.index)))
     }
-    fn lt(&self, reg: &mut Register) -> bool {
-        self.left.resolve(reg) < self.right.resolve(reg)
+
+    /// Writes one row per executed statement, holding the condition outcome
+    /// and the value of every register after the statement.
+    ///
+    /// ```text
+    /// statement,condition,a,b,c
+    /// 1,false,0,0,0
+    /// 2,true,1,0,0
+    /// ```
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut names: Vec<&str> = self.reg.keys().map(|s| s.as_str()).collect();
+        names.sort();
+        write!(w, "statement,condition")?;
+        for name in &names {
+            write!(w, ",{}", name)?;
+        }
+        writeln!(w)?;
+
+        let zero = BigInt::from(0);
+        let mut values: HashMap<&str, &BigInt> = HashMap::new();
+        for (i, x) in self.trace.iter().enumerate() {
+            values.insert(&x.target, &x.value);
+            write!(w, "{},{}", i + 1, x.condition)?;
+            for name in &names {
+                write!(w, ",{}", values.get(name).unwrap_or(&&zero))?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// The record of a single executed statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Register the statement modifies.
+    pub target: String,
+
+    /// Outcome of the condition.
+    pub condition: bool,
+
+    /// Value of the target after the statement.
+    pub value: BigInt,
+}
+
+/// A value of a register, and the 0-based index of the statement that set it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extreme {
+    pub index: usize,
+    pub value: BigInt,
+}
+
+impl fmt::Display for Extreme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (statement {})", self.value, self.index + 1)
     }
-    fn le(&self, reg: &mut Register) -> bool {
-        self.left.resolve(reg) <= self.right.resolve(reg)
+}
+
+/// Every value written to a register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    values: Vec<(usize, BigInt)>,
+    min: Extreme,
+    max: Extreme,
+}
+
+impl History {
+    fn new(index: usize, value: BigInt) -> Self {
+        History {
+            values: Vec::new(),
+            min: Extreme{ index, value: value.clone() },
+            max: Extreme{ index, value },
+        }
     }
-    fn ge(&self, reg: &mut Register) -> bool {
-        !self.lt(reg)
+
+    fn push(&mut self, index: usize, value: BigInt) {
+        if value < self.min.value {
+            self.min = Extreme{ index, value: value.clone() };
+        }
+        if value > self.max.value {
+            self.max = Extreme{ index, value: value.clone() };
+        }
+        self.values.push((index, value));
     }
-    fn gt(&self, reg: &mut Register) -> bool {
-        !self.le(reg)
+
+    /// Returns the statement index and new value of every write, in order.
+    pub fn values(&self) -> &[(usize, BigInt)] {
+        &self.values
     }
-    fn inc(&self, reg: &mut Register) -> isize {
-        let id = String::from(self.left.ident());
-        let n = self.right.resolve(reg);
-        let r = reg.entry(id).or_insert(0);
-        *r += n;
-        *r
+
+    /// Returns the lowest value written, first occurrence.
+    pub fn min(&self) -> &Extreme {
+        &self.min
     }
-    fn dec(&self, reg: &mut Register) -> isize {
-        let id = String::from(self.left.ident());
-        let n = self.right.resolve(reg);
-        let r = reg.entry(id).or_insert(0);
-        *r -= n;
-        *r
+
+    /// Returns the highest value written, first occurrence.
+    pub fn max(&self) -> &Extreme {
+        &self.max
     }
 }
 
+/// The result of a static analysis over a `Program`.
 #[derive(Debug)]
+pub struct Analysis {
+    registers: BTreeSet<String>,
+    warnings: Vec<Warning>,
+}
+
+impl Analysis {
+    /// Analyzes the program without running it.
+    ///
+    /// Statements are considered in order, regardless of whether
+    /// their conditions would hold at runtime.
+    pub fn new(prog: &[Statement]) -> Self {
+        let mut written = HashSet::new();
+        let mut read = HashSet::new();
+        let mut warnings = Vec::new();
+
+        for (i, x) in prog.iter().enumerate() {
+            if x.condition.is_constant() {
+                if let Ok(value) = x.condition.eval(&Register::new(), Overflow::Unbounded).and_then(|c| c.as_bool()) {
+                    warnings.push(Warning::ConstantCondition{ index: i, value });
+                }
+            }
+            for name in x.reads() {
+                if read.insert(name) && !written.contains(name) {
+                    warnings.push(Warning::ReadBeforeWrite{ index: i, name: String::from(name) });
+                }
+            }
+            written.insert(x.target());
+        }
+
+        let registers: BTreeSet<String> = prog.iter()
+            .flat_map(|x| x.reads().into_iter().chain(Some(x.target())))
+            .map(String::from)
+            .collect();
+        warnings.extend(registers.iter()
+            .filter(|s| !read.contains(s.as_str()))
+            .map(|s| Warning::NeverRead{ name: s.clone() }));
+
+        Analysis {
+            registers,
+            warnings,
+        }
+    }
+
+    /// Returns the names of all registers used in the program, in sorted order.
+    pub fn registers(&self) -> &BTreeSet<String> {
+        &self.registers
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+}
+
+/// A potential problem in a `Program`, found by `Analysis`.
+/// Statement indices are 0-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// Register is read before any statement writes it, so it defaults to 0.
+    ReadBeforeWrite{ index: usize, name: String },
+
+    /// Condition does not depend on any register.
+    ConstantCondition{ index: usize, value: bool },
+
+    /// Register is written but its value is never used.
+    NeverRead{ name: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::ReadBeforeWrite{ index, ref name } =>
+                write!(f, "statement {}: register {} is read before it is written", index + 1, name),
+            Warning::ConstantCondition{ index, value } =>
+                write!(f, "statement {}: condition is always {}", index + 1, value),
+            Warning::NeverRead{ ref name } =>
+                write!(f, "register {} is written but never read", name),
+        }
+    }
+}
+
+/// An expression, which evaluates to a number or a boolean.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Operand(Operand),
+    Unary(UnaryOperator, Box<Expr>),
+    Binary(Box<BinaryOperation>),
+}
+
+impl Expr {
+    /// Evaluates the expression, reading registers that have not been written yet as 0.
+    pub fn eval(&self, reg: &Register, overflow: Overflow) -> Result<Constant, EvalError> {
+        match *self {
+            Expr::Operand(ref o) => o.eval(reg),
+            Expr::Unary(UnaryOperator::Neg, ref e) => {
+                let n = e.resolve(reg, overflow)?;
+                Ok(Constant::Num(Operator::Minus.compute(&BigInt::from(0), &n, overflow)?))
+            }
+            Expr::Unary(UnaryOperator::Not, ref e) => Ok(Constant::Bool(!e.eval(reg, overflow)?.as_bool()?)),
+            Expr::Binary(ref op) => op.eval(reg, overflow),
+        }
+    }
+
+    /// Evaluates the expression, which must result in a number.
+    pub fn resolve(&self, reg: &Register, overflow: Overflow) -> Result<BigInt, EvalError> {
+        self.eval(reg, overflow)?.as_num()
+    }
+
+    /// Returns the register name if the expression is a single register.
+    pub fn ident(&self) -> Option<&str> {
+        match *self {
+            Expr::Operand(ref o) => o.ident(),
+            _ => None,
+        }
+    }
+
+    /// Returns the type the expression evaluates to, assuming its operands
+    /// have the types their operators require. The parser checks this.
+    pub fn ty(&self) -> Type {
+        match *self {
+            Expr::Operand(Operand::Ident(..)) => Type::Num,
+            Expr::Operand(Operand::Const(ref c)) => c.ty(),
+            Expr::Unary(UnaryOperator::Neg, _) => Type::Num,
+            Expr::Unary(UnaryOperator::Not, _) => Type::Bool,
+            Expr::Binary(ref op) => op.op.ty(),
+        }
+    }
+
+    /// Appends the names of all registers in the expression to `out`,
+    /// from left to right.
+    pub fn registers<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match *self {
+            Expr::Operand(Operand::Ident(ref s)) => out.push(s),
+            Expr::Operand(Operand::Const(..)) => {}
+            Expr::Unary(_, ref e) => e.registers(out),
+            Expr::Binary(ref op) => {
+                op.left.registers(out);
+                op.right.registers(out);
+            }
+        }
+    }
+
+    /// Returns true if the expression does not depend on any register.
+    pub fn is_constant(&self) -> bool {
+        let mut names = Vec::new();
+        self.registers(&mut names);
+        names.is_empty()
+    }
+
+    /// Returns the binding strength of the outermost operator,
+    /// which is used to decide where parentheses are needed.
+    fn precedence(&self) -> u8 {
+        match *self {
+            Expr::Operand(..) => 8,
+            Expr::Unary(UnaryOperator::Neg, _) => 7,
+            Expr::Unary(UnaryOperator::Not, _) => 3,
+            Expr::Binary(ref op) => op.op.precedence(),
+        }
+    }
+
+    fn fmt_inner(&self, f: &mut fmt::Formatter, min: u8) -> fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Operand(ref o) => write!(f, "{}", o),
+            Expr::Unary(op, ref e) => {
+                write!(f, "{}", op)?;
+                e.fmt_inner(f, self.precedence())
+            }
+            Expr::Binary(ref op) => write!(f, "{}", op),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryOperation {
+    op: Operator,
+    left: Expr,
+    right: Expr,
+}
+
+impl BinaryOperation {
+    pub fn eval(&self, reg: &Register, overflow: Overflow) -> Result<Constant, EvalError> {
+        let (left, right) = (&self.left, &self.right);
+        let b = match self.op {
+            Operator::And => left.eval(reg, overflow)?.as_bool()? && right.eval(reg, overflow)?.as_bool()?,
+            Operator::Or => left.eval(reg, overflow)?.as_bool()? || right.eval(reg, overflow)?.as_bool()?,
+            Operator::Eq | Operator::Ne => {
+                let (l, r) = (left.eval(reg, overflow)?, right.eval(reg, overflow)?);
+                if l.ty() != r.ty() {
+                    return Err(EvalError::TypeMismatch{ expected: l.ty(), found: r });
+                }
+                (l == r) == (self.op == Operator::Eq)
+            }
+            Operator::Lt => left.resolve(reg, overflow)? < right.resolve(reg, overflow)?,
+            Operator::Gt => left.resolve(reg, overflow)? > right.resolve(reg, overflow)?,
+            Operator::Le => left.resolve(reg, overflow)? <= right.resolve(reg, overflow)?,
+            Operator::Ge => left.resolve(reg, overflow)? >= right.resolve(reg, overflow)?,
+            op => {
+                let (a, b) = (left.resolve(reg, overflow)?, right.resolve(reg, overflow)?);
+                return Ok(Constant::Num(op.compute(&a, &b, overflow)?));
+            }
+        };
+        Ok(Constant::Bool(b))
+    }
+}
+
+impl fmt::Display for BinaryOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Binary operators are left-associative, so the right side needs
+        // parentheses even when it binds equally strong. Comparisons do not
+        // chain, so the same holds for their left side.
+        let p = self.op.precedence();
+        self.left.fmt_inner(f, if self.op.is_comparison() { p + 1 } else { p })?;
+        write!(f, " {} ", self.op)?;
+        self.right.fmt_inner(f, p + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
+    // Assignments
     Inc,
     Dec,
+    Set,
+    Mul,
+    Div,
+    Mod,
+
+    // Comparisons
     Eq,
     Ne,
     Lt,
     Gt,
     Ge,
     Le,
+
+    // Logic
+    And,
+    Or,
+
+    // Arithmetic
+    Plus,
+    Minus,
+    Times,
+    Quot,
+    Rem,
+}
+
+impl Operator {
+    pub fn is_assignment(&self) -> bool {
+        matches!(*self, Operator::Inc | Operator::Dec | Operator::Set | Operator::Mul | Operator::Div | Operator::Mod)
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(*self, Operator::Eq | Operator::Ne | Operator::Lt | Operator::Gt | Operator::Ge | Operator::Le)
+    }
+
+    /// Applies an arithmetic or assignment operator, where `a` is the
+    /// current value of the register for the latter. The result is
+    /// fitted into 64 bits according to the overflow policy.
+    /// Comparisons and logical operators fail with `EvalError::NotArithmetic`.
+    pub fn compute(&self, a: &BigInt, b: &BigInt, overflow: Overflow) -> Result<BigInt, EvalError> {
+        let n = match *self {
+            Operator::Plus | Operator::Inc => a + b,
+            Operator::Minus | Operator::Dec => a - b,
+            Operator::Times | Operator::Mul => a * b,
+            Operator::Quot | Operator::Div | Operator::Rem | Operator::Mod if b.sign() == Sign::NoSign => {
+                return Err(EvalError::DivideByZero{ op: *self, left: a.clone() });
+            }
+            Operator::Quot | Operator::Div => a / b,
+            Operator::Rem | Operator::Mod => a % b,
+            Operator::Set => b.clone(),
+            _ => return Err(EvalError::NotArithmetic{ op: *self }),
+        };
+        overflow.apply(n).ok_or_else(|| EvalError::Overflow{ op: *self, left: a.clone(), right: b.clone() })
+    }
+
+    /// Returns the type of the result.
+    pub fn ty(&self) -> Type {
+        match *self {
+            Operator::Eq | Operator::Ne | Operator::Lt | Operator::Gt | Operator::Ge | Operator::Le => Type::Bool,
+            Operator::And | Operator::Or => Type::Bool,
+            _ => Type::Num,
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match *self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Eq | Operator::Ne | Operator::Lt | Operator::Gt | Operator::Ge | Operator::Le => 4,
+            Operator::Plus | Operator::Minus => 5,
+            Operator::Times | Operator::Quot | Operator::Rem => 6,
+            _ => 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Operator::Inc => "inc",
+            Operator::Dec => "dec",
+            Operator::Set => "set",
+            Operator::Mul => "mul",
+            Operator::Div => "div",
+            Operator::Mod => "mod",
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Lt => "<",
+            Operator::Gt => ">",
+            Operator::Le => "<=",
+            Operator::Ge => ">=",
+            Operator::And => "and",
+            Operator::Or => "or",
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Times => "*",
+            Operator::Quot => "/",
+            Operator::Rem => "%",
+        }
+    }
 }
 
 impl FromStr for Operator {
@@ -203,18 +705,50 @@ impl FromStr for Operator {
         match s {
             "inc" => Ok(Operator::Inc),
             "dec" => Ok(Operator::Dec),
+            "set" => Ok(Operator::Set),
+            "mul" => Ok(Operator::Mul),
+            "div" => Ok(Operator::Div),
+            "mod" => Ok(Operator::Mod),
             "==" => Ok(Operator::Eq),
             "!=" => Ok(Operator::Ne),
             "<" => Ok(Operator::Lt),
             ">" => Ok(Operator::Gt),
             "<=" => Ok(Operator::Le),
             ">=" => Ok(Operator::Ge),
+            "and" => Ok(Operator::And),
+            "or" => Ok(Operator::Or),
+            "+" => Ok(Operator::Plus),
+            "-" => Ok(Operator::Minus),
+            "*" => Ok(Operator::Times),
+            "/" => Ok(Operator::Quot),
+            "%" => Ok(Operator::Rem),
             _ => Err(ParseError::with_msg(s, "unknown operator")),
         }
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Neg,
+    Not,
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnaryOperator::Neg => f.write_str("-"),
+            UnaryOperator::Not => f.write_str("not "),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operand {
     Ident(String),
     Const(Constant),
@@ -222,23 +756,27 @@ pub enum Operand {
 
 impl Operand {
     pub fn is_ident(&self) -> bool {
-        match *self {
-            Operand::Ident(..) => true,
-            _ => false,
-        }
+        matches!(*self, Operand::Ident(..))
     }
 
-    pub fn ident(&self) -> &str {
+    pub fn ident(&self) -> Option<&str> {
         match *self {
-            Operand::Ident(ref s) => s.as_str(),
-            _ => panic!("cannot unwrap Operand"),
+            Operand::Ident(ref s) => Some(s.as_str()),
+            _ => None,
         }
     }
 
-    pub fn resolve(&self, reg: &mut Register) -> isize {
+    /// Returns the value of the operand, which must be a number.
+    pub fn resolve(&self, reg: &Register) -> Result<BigInt, EvalError> {
+        self.eval(reg)?.as_num()
+    }
+
+    /// Returns the value of the operand, where registers that have not
+    /// been written yet are 0. The register map is left untouched.
+    pub fn eval(&self, reg: &Register) -> Result<Constant, EvalError> {
         match *self {
-            Operand::Ident(ref s) => *reg.entry(s.clone()).or_insert(0),
-            Operand::Const(ref c) => c.as_num(),
+            Operand::Ident(ref s) => Ok(Constant::Num(reg.get(s).cloned().unwrap_or_else(|| BigInt::from(0)))),
+            Operand::Const(ref c) => Ok(c.clone()),
         }
     }
 }
@@ -260,24 +798,40 @@ impl FromStr for Operand {
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::Ident(ref s) => f.write_str(s),
+            Operand::Const(ref c) => write!(f, "{}", c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Constant {
-    Num(isize),
+    Num(BigInt),
     Bool(bool),
 }
 
 impl Constant {
-    pub fn as_num(&self) -> isize {
+    pub fn ty(&self) -> Type {
+        match *self {
+            Constant::Num(..) => Type::Num,
+            Constant::Bool(..) => Type::Bool,
+        }
+    }
+
+    pub fn as_num(&self) -> Result<BigInt, EvalError> {
         match *self {
-            Constant::Num(n) => n,
-            Constant::Bool(b) => if b { 1 } else { 0 },
+            Constant::Num(ref n) => Ok(n.clone()),
+            Constant::Bool(..) => Err(EvalError::TypeMismatch{ expected: Type::Num, found: self.clone() }),
         }
     }
 
-    pub fn as_bool(&self) -> bool {
+    pub fn as_bool(&self) -> Result<bool, EvalError> {
         match *self {
-            Constant::Num(..) => panic!("cannot convert number to bool"),
-            Constant::Bool(b) => b,
+            Constant::Num(..) => Err(EvalError::TypeMismatch{ expected: Type::Bool, found: self.clone() }),
+            Constant::Bool(b) => Ok(b),
         }
     }
 }
@@ -291,7 +845,7 @@ impl FromStr for Constant {
     /// If this precondition is not fulfilled, the result will
     /// be an `Ident` containing strings.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(n) = s.parse::<isize>() {
+        if let Ok(n) = s.parse::<BigInt>() {
             Ok(Constant::Num(n))
         } else {
             match s {
@@ -303,6 +857,438 @@ impl FromStr for Constant {
     }
 }
 
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Constant::Num(ref n) => write!(f, "{}", n),
+            Constant::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// The type of an expression or constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Num,
+    Bool,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Num => f.write_str("number"),
+            Type::Bool => f.write_str("boolean"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// Error due to a value having a different type than its use requires.
+    TypeMismatch{
+        expected: Type,
+        found: Constant,
+    },
+
+    /// Error due to dividing by zero, or taking the remainder of it.
+    DivideByZero{
+        op: Operator,
+        left: BigInt,
+    },
+
+    /// Error due to a result not fitting into 64 bits under `Overflow::Checked`.
+    Overflow{
+        op: Operator,
+        left: BigInt,
+        right: BigInt,
+    },
+
+    /// Error due to computing a number with a comparison or a logical operator.
+    NotArithmetic{
+        op: Operator,
+    },
+}
+
+impl error::Error for EvalError {
+    fn description(&self) -> &str {
+        match *self {
+            EvalError::TypeMismatch{..} => "value has the wrong type",
+            EvalError::DivideByZero{..} => "division by zero",
+            EvalError::Overflow{..} => "arithmetic overflow",
+            EvalError::NotArithmetic{..} => "operator does not compute a number",
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EvalError::TypeMismatch{ expected, ref found } => write!(f, "expected {}, found {} {}", expected, found.ty(), found),
+            EvalError::DivideByZero{ op, ref left } => write!(f, "division by zero in: {} {} 0", left, op),
+            EvalError::Overflow{ op, ref left, ref right } => write!(f, "arithmetic overflow in: {} {} {}", left, op, right),
+            EvalError::NotArithmetic{ op } => write!(f, "operator {} does not compute a number", op),
+        }
+    }
+}
+
+/// Error due to a statement that cannot be executed, at a 0-based index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunError {
+    pub index: usize,
+    pub cause: EvalError,
+}
+
+impl error::Error for RunError {
+    fn description(&self) -> &str { "cannot execute statement" }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "statement {}: {}", self.index + 1, self.cause)
+    }
+}
+
+/// How to handle results of register arithmetic that do not fit into 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Fail with `EvalError::Overflow`.
+    #[default]
+    Checked,
+
+    /// Wrap around in two's complement.
+    Wrapping,
+
+    /// Clamp to the smallest or largest value.
+    Saturating,
+
+    /// Keep the result, however large it is.
+    Unbounded,
+}
+
+impl Overflow {
+    /// Fits `n` into the range of `i64`, or returns `None` if it does not fit
+    /// and the policy is `Checked`.
+    pub fn apply(&self, n: BigInt) -> Option<BigInt> {
+        let (min, max) = (BigInt::from(i64::MIN), BigInt::from(i64::MAX));
+        if n >= min && n <= max {
+            return Some(n);
+        }
+        match *self {
+            Overflow::Checked => None,
+            Overflow::Wrapping => {
+                // The lowest 8 bytes in two's complement are exactly the wrapped value.
+                let bytes = n.to_signed_bytes_le();
+                let mut low = [0u8; 8];
+                low.copy_from_slice(&bytes[..8]);
+                Some(BigInt::from(i64::from_le_bytes(low)))
+            }
+            Overflow::Saturating => Some(if n < min { min } else { max }),
+            Overflow::Unbounded => Some(n),
+        }
+    }
+}
+
+impl FromStr for Overflow {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checked" => Ok(Overflow::Checked),
+            "wrapping" => Ok(Overflow::Wrapping),
+            "saturating" => Ok(Overflow::Saturating),
+            "unbounded" => Ok(Overflow::Unbounded),
+            _ => Err(ParseError::with_msg(s, "unknown overflow policy")),
+        }
+    }
+}
+
+/// A token of a statement, as produced by `tokenize`.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A register name or keyword.
+    Word(String),
+
+    /// The digits of a number, without sign.
+    Num(String),
+
+    /// An operator or parenthesis.
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Word(ref s) | Token::Num(ref s) => write!(f, "'{}'", s),
+            Token::Symbol(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 13] = ["==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "(", ")"];
+
+const KEYWORDS: [&str; 6] = ["if", "and", "or", "not", "true", "false"];
+
+/// Splits a statement into tokens, each paired with its 1-based column.
+fn tokenize(s: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().enumerate().peekable();
+    while let Some(&(col, (i, c))) = chars.peek() {
+        let rest = &s[i..];
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_alphabetic() || c == '_' {
+            let n = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            tokens.push((Token::Word(String::from(&rest[..n])), col + 1));
+            rest[..n].chars().for_each(|_| { chars.next(); });
+        } else if c.is_ascii_digit() {
+            let n = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            tokens.push((Token::Num(String::from(&rest[..n])), col + 1));
+            rest[..n].chars().for_each(|_| { chars.next(); });
+        } else if let Some(sym) = SYMBOLS.iter().find(|x| rest.starts_with(*x)) {
+            tokens.push((Token::Symbol(sym), col + 1));
+            sym.chars().for_each(|_| { chars.next(); });
+        } else {
+            return Err(ParseError::at(s, col + 1, "unexpected character"));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive-descent parser for a single statement.
+///
+/// The grammar, from loosest to tightest binding, is:
+///
+/// ```text
+/// statement := IDENT assign expr 'if' expr
+/// expr      := and ('or' and)*
+/// and       := not ('and' not)*
+/// not       := 'not' not | cmp
+/// cmp       := sum (('==' | '!=' | '<' | '>' | '<=' | '>=') sum)?
+/// sum       := term (('+' | '-') term)*
+/// term      := unary (('*' | '/' | '%') unary)*
+/// unary     := '-' unary | primary
+/// primary   := NUM | IDENT | 'true' | 'false' | '(' expr ')'
+/// ```
+struct Parser<'a> {
+    data: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a str) -> Result<Self, ParseError> {
+        Ok(Parser {
+            data,
+            tokens: tokenize(data)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.0)
+    }
+
+    /// Returns the column of the next token, or one past the end.
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos)
+            .map(|t| t.1)
+            .unwrap_or_else(|| self.data.chars().count() + 1)
+    }
+
+    fn error(&self, msg: &'static str) -> ParseError {
+        ParseError::at(self.data, self.column(), msg)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).map(|t| t.0.clone());
+        if tok.is_some() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    /// Consumes the next token if it is the given word or symbol.
+    fn accept(&mut self, s: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Word(w)) => w == s,
+            Some(&Token::Symbol(sym)) => sym == s,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Consumes the next token if it is one of the given operators,
+    /// and returns the operator together with its column.
+    fn accept_op(&mut self, ops: &[&str]) -> Option<(Operator, usize)> {
+        let column = self.column();
+        let op = ops.iter().find(|op| self.accept(op))?;
+        op.parse().ok().map(|op| (op, column))
+    }
+
+    /// Combines two operands, checking that their types fit the operator.
+    fn binary(&self, op: Operator, left: Expr, right: Expr, column: usize) -> Result<Expr, ParseError> {
+        let (l, r) = (left.ty(), right.ty());
+        let msg = match op {
+            Operator::And | Operator::Or if l != Type::Bool || r != Type::Bool => Some("operands of logical operator must be booleans"),
+            Operator::Eq | Operator::Ne if l != r => Some("operands of comparison must have the same type"),
+            Operator::Lt | Operator::Gt | Operator::Le | Operator::Ge if l != Type::Num || r != Type::Num => Some("operands of comparison must be numbers"),
+            Operator::Plus | Operator::Minus | Operator::Times | Operator::Quot | Operator::Rem if l != Type::Num || r != Type::Num => Some("operands of arithmetic must be numbers"),
+            _ => None,
+        };
+        match msg {
+            Some(msg) => Err(ParseError::at(self.data, column, msg)),
+            None => Ok(Expr::Binary(Box::new(BinaryOperation{ op, left, right }))),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        let target = match self.peek() {
+            Some(Token::Word(w)) if !KEYWORDS.contains(&w.as_str()) => w.clone(),
+            Some(Token::Num(..)) => return Err(self.error("cannot assign to a number, expecting register name")),
+            Some(Token::Word(w)) if w == "true" || w == "false" => return Err(self.error("cannot assign to a boolean, expecting register name")),
+            _ => return Err(self.error("expecting register name")),
+        };
+        self.pos += 1;
+
+        let op = match self.peek() {
+            Some(Token::Word(w)) => w.parse::<Operator>().ok().filter(|op| op.is_assignment()),
+            _ => None,
+        };
+        let op = op.ok_or_else(|| self.error("expecting one of inc, dec, set, mul, div, or mod"))?;
+        self.pos += 1;
+
+        let column = self.column();
+        let value = self.expr()?;
+        if value.ty() != Type::Num {
+            return Err(ParseError::at(self.data, column, "value must be a number"));
+        }
+        if !self.accept("if") {
+            return Err(self.error("expecting 'if' or operator"));
+        }
+        let column = self.column();
+        let condition = self.expr()?;
+        if condition.ty() != Type::Bool {
+            return Err(ParseError::at(self.data, column, "condition must be a boolean"));
+        }
+        if self.peek().is_some() {
+            return Err(self.error("expecting end of statement or operator"));
+        }
+
+        Ok(Statement{ target, op, value, condition })
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.and()?;
+        while let Some((op, column)) = self.accept_op(&["or"]) {
+            let right = self.and()?;
+            left = self.binary(op, left, right, column)?;
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.not()?;
+        while let Some((op, column)) = self.accept_op(&["and"]) {
+            let right = self.not()?;
+            left = self.binary(op, left, right, column)?;
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        let column = self.column();
+        if !self.accept("not") {
+            return self.cmp();
+        }
+        let e = self.not()?;
+        if e.ty() != Type::Bool {
+            return Err(ParseError::at(self.data, column, "operand of 'not' must be a boolean"));
+        }
+        Ok(Expr::Unary(UnaryOperator::Not, Box::new(e)))
+    }
+
+    fn cmp(&mut self) -> Result<Expr, ParseError> {
+        let left = self.sum()?;
+        match self.accept_op(&["==", "!=", "<=", ">=", "<", ">"]) {
+            Some((op, column)) => {
+                let right = self.sum()?;
+                self.binary(op, left, right, column)
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.term()?;
+        while let Some((op, column)) = self.accept_op(&["+", "-"]) {
+            let right = self.term()?;
+            left = self.binary(op, left, right, column)?;
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.unary()?;
+        while let Some((op, column)) = self.accept_op(&["*", "/", "%"]) {
+            let right = self.unary()?;
+            left = self.binary(op, left, right, column)?;
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if !self.accept("-") {
+            return self.primary();
+        }
+        let column = self.tokens[self.pos - 1].1;
+
+        // A minus directly in front of a number is part of the constant,
+        // so that it is not subject to the overflow policy on its own.
+        if let Some(Token::Num(digits)) = self.peek() {
+            let n = -digits.parse::<BigInt>().expect("digits are a number");
+            self.pos += 1;
+            return Ok(Expr::Operand(Operand::Const(Constant::Num(n))));
+        }
+        let e = self.unary()?;
+        if e.ty() != Type::Num {
+            return Err(ParseError::at(self.data, column, "operand of '-' must be a number"));
+        }
+        Ok(Expr::Unary(UnaryOperator::Neg, Box::new(e)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let column = self.column();
+        match self.next() {
+            Some(Token::Num(digits)) => {
+                let n = digits.parse::<BigInt>().expect("digits are a number");
+                Ok(Expr::Operand(Operand::Const(Constant::Num(n))))
+            }
+            Some(Token::Word(ref w)) if w == "true" => Ok(Expr::Operand(Operand::Const(Constant::Bool(true)))),
+            Some(Token::Word(ref w)) if w == "false" => Ok(Expr::Operand(Operand::Const(Constant::Bool(false)))),
+            Some(Token::Word(w)) => {
+                if KEYWORDS.contains(&w.as_str()) {
+                    Err(ParseError::at(self.data, column, "unexpected keyword, expecting operand"))
+                } else {
+                    Ok(Expr::Operand(Operand::Ident(w)))
+                }
+            }
+            Some(Token::Symbol("(")) => {
+                let e = self.expr()?;
+                if self.accept(")") {
+                    Ok(e)
+                } else {
+                    Err(self.error("expecting ')'"))
+                }
+            }
+            Some(_) => Err(ParseError::at(self.data, column, "unexpected symbol, expecting operand")),
+            None => Err(self.error("unexpected end of statement, expecting operand")),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ParseError {
     msg: &'static str,
@@ -321,11 +1307,20 @@ impl ParseError {
 
     pub fn with_msg(data: &str, msg: &'static str) -> Self {
         ParseError {
-            msg: msg,
+            msg,
             data: String::from(data),
             column: 0,
         }
     }
+
+    /// Creates an error at the 1-based `column` of `data`.
+    pub fn at(data: &str, column: usize, msg: &'static str) -> Self {
+        ParseError {
+            msg,
+            data: String::from(data),
+            column,
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -338,6 +1333,225 @@ impl error::Error for ParseError {
     fn description(&self) -> &str { self.msg }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "b inc 5 if a > 1
+a inc 1 if b < 5
+c dec -10 if a >= 1
+c inc -20 if c == 10";
+
+    fn num(n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    fn run(s: &str) -> Register {
+        let prog: Program = s.lines().map(|x| x.parse().unwrap()).collect();
+        let mut reg = Register::new();
+        prog.iter().for_each(|x| { x.apply(&mut reg, Overflow::Checked).unwrap(); });
+        reg
+    }
+
+    #[test]
+    fn test_example() {
+        let reg = run(EXAMPLE);
+        assert_eq!(reg.values().max(), Some(&num(1)));
+        assert_eq!(reg["c"], num(-10));
+    }
+
+    #[test]
+    fn test_expressions() {
+        let tests = vec![
+            ("x set 2 + 3 * 4 if true", 14),
+            ("x set (2 + 3) * 4 if true", 20),
+            ("x set 10 - 4 - 3 if true", 3),
+            ("x set -(2 - 5) if 1 < 2", 3),
+            ("x set 17 % 5 if true", 2),
+            ("x inc 3 if 1 > 2 or 2 > 1 and not 1 == 2", 3),
+            ("x inc 3 if (1 > 2 or 2 > 1) and 1 == 2", 0),
+            ("x inc 3 if not not true", 3),
+            ("x set 7 if true\nx mul 3 if x == 7\nx div 2 if true\nx mod 4 if x > 0", 2),
+            ("y set 5 if true\nx dec y * 2 if y != 0", -10),
+        ];
+
+        for t in tests {
+            assert_eq!(run(t.0).get("x").cloned().unwrap_or_else(|| num(0)), num(t.1), "input = {:?}", t.0);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let tests = vec![
+            ("c dec -10 if a >= 1", "c dec -10 if a >= 1"),
+            ("x set (a+b)*2 if not(a==b or c<0)", "x set (a + b) * 2 if not (a == b or c < 0)"),
+            ("x set a - (b - c) if (a > 1) and true", "x set a - (b - c) if a > 1 and true"),
+            ("x set - - a if not a > 1 or b < 2", "x set --a if not a > 1 or b < 2"),
+            ("x inc 1 if (a > 1) == true", "x inc 1 if (a > 1) == true"),
+            ("x inc 1 if (a == b) != (c < d)", "x inc 1 if (a == b) != (c < d)"),
+            ("x inc 1 if ((a + 1) > b * 2) == (b <= 3)", "x inc 1 if (a + 1 > b * 2) == (b <= 3)"),
+        ];
+
+        for t in tests {
+            let stmt: Statement = t.0.parse().unwrap();
+            assert_eq!(stmt.to_string(), t.1);
+            assert_eq!(stmt.to_string().parse::<Statement>().unwrap(), stmt, "input = {:?}", t.0);
+        }
+    }
+
+    #[test]
+    fn test_executor() {
+        let prog: Program = EXAMPLE.lines().map(|x| x.parse().unwrap()).collect();
+        let mut exec = Executor::new();
+        exec.run(&prog).unwrap();
+
+        let conditions: Vec<bool> = exec.trace().iter().map(|x| x.condition).collect();
+        assert_eq!(conditions, vec![false, true, true, true]);
+        assert_eq!(exec.history("b"), None);
+        let c = exec.history("c").unwrap();
+        assert_eq!(c.values(), &[(2, num(10)), (3, num(-10))]);
+        assert_eq!(c.min(), &Extreme{ index: 3, value: num(-10) });
+        assert_eq!(c.max(), &Extreme{ index: 2, value: num(10) });
+        assert_eq!(exec.max(), Some(("c", &Extreme{ index: 2, value: num(10) })));
+
+        let prog: Program = "a dec 5 if a == 0\nb inc -2 if a < 0".lines().map(|x| x.parse().unwrap()).collect();
+        let mut negative = Executor::new();
+        negative.run(&prog).unwrap();
+        assert_eq!(negative.history("b").unwrap().max().value, num(-2));
+        assert_eq!(negative.max(), None);
+
+        let mut csv = Vec::new();
+        exec.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "statement,condition,a,b,c
+1,false,0,0,0
+2,true,1,0,0
+3,true,1,0,10
+4,true,1,0,-10
+");
+    }
+
+    #[test]
+    fn test_analysis() {
+        let prog: Program = "b inc 5 if a > 1
+a inc 1 if b < 5
+c dec -10 if 1 >= 2
+d set a + e if true or false
+c inc -20 if c == 10".lines().map(|x| x.parse().unwrap()).collect();
+        let analysis = Analysis::new(&prog);
+
+        let names: Vec<&str> = analysis.registers().iter().map(|s| s.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(analysis.warnings(), &[
+            Warning::ReadBeforeWrite{ index: 0, name: String::from("a") },
+            Warning::ReadBeforeWrite{ index: 0, name: String::from("b") },
+            Warning::ConstantCondition{ index: 2, value: false },
+            Warning::ReadBeforeWrite{ index: 2, name: String::from("c") },
+            Warning::ConstantCondition{ index: 3, value: true },
+            Warning::ReadBeforeWrite{ index: 3, name: String::from("e") },
+            Warning::NeverRead{ name: String::from("d") },
+        ]);
+
+        // Every operator but `set` reads its target before writing it.
+        let tests = vec![
+            ("x inc 1 if true", vec!["x"]),
+            ("x set y if z > 0", vec!["z", "y"]),
+            ("x mul x if y > 0", vec!["y", "x", "x"]),
+        ];
+        for t in tests {
+            let stmt: Statement = t.0.parse().unwrap();
+            assert_eq!(stmt.reads(), t.1, "input = {:?}", t.0);
+        }
+        let prog: Program = vec!["x inc 1 if true".parse().unwrap()];
+        assert_eq!(Analysis::new(&prog).warnings(), &[
+            Warning::ConstantCondition{ index: 0, value: true },
+            Warning::ReadBeforeWrite{ index: 0, name: String::from("x") },
+        ]);
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut reg = Register::new();
+        reg.insert(String::from("a"), num(4));
+        let tests = vec![
+            ("x set a / 0 if true", Err(EvalError::DivideByZero{ op: Operator::Quot, left: num(4) })),
+            ("x mod 0 if true", Err(EvalError::DivideByZero{ op: Operator::Mod, left: num(0) })),
+            ("x set a / 2 if (a > 1) == true", Ok(Some(num(2)))),
+            ("x set a if b > 1", Ok(None)),
+        ];
+
+        for t in tests {
+            let stmt: Statement = t.0.parse().unwrap();
+            assert_eq!(stmt.apply(&mut reg.clone(), Overflow::Checked), t.1, "input = {:?}", t.0);
+        }
+
+        // Reading registers leaves the map untouched.
+        let expr = Expr::Operand(Operand::Ident(String::from("b")));
+        assert_eq!(expr.eval(&reg, Overflow::Checked), Ok(Constant::Num(num(0))));
+        assert_eq!(reg.len(), 1);
+        assert_eq!(Constant::Num(num(1)).as_bool(), Err(EvalError::TypeMismatch{ expected: Type::Bool, found: Constant::Num(num(1)) }));
+        assert_eq!(Operator::Lt.compute(&num(1), &num(2), Overflow::Checked), Err(EvalError::NotArithmetic{ op: Operator::Lt }));
+        assert_eq!(Operator::And.compute(&num(1), &num(2), Overflow::Checked), Err(EvalError::NotArithmetic{ op: Operator::And }));
+        assert_eq!(Expr::Operand(Operand::Const(Constant::Num(num(1)))).ident(), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let prog: Program = "a set 9223372036854775807 if true
+b set -9223372036854775807 if true
+a inc 2 if true
+b dec 2 if true
+c set a * 2 if a > 0".lines().map(|x| x.parse().unwrap()).collect();
+        let big = "9223372036854775809".parse::<BigInt>().unwrap();
+        let tests = vec![
+            (Overflow::Wrapping, num(i64::MIN + 1), num(i64::MAX), None),
+            (Overflow::Saturating, num(i64::MAX), num(i64::MIN), Some(num(i64::MAX))),
+            (Overflow::Unbounded, big.clone(), -big.clone(), Some(big * 2)),
+        ];
+
+        for t in tests {
+            let mut exec = Executor::with_overflow(t.0);
+            exec.run(&prog).unwrap();
+            assert_eq!(exec.registers()["a"], t.1, "overflow = {:?}", t.0);
+            assert_eq!(exec.registers()["b"], t.2, "overflow = {:?}", t.0);
+            assert_eq!(exec.history("c").map(|h| h.max().value.clone()), t.3, "overflow = {:?}", t.0);
+        }
+
+        let mut exec = Executor::new();
+        assert_eq!(exec.run(&prog), Err(RunError{
+            index: 2,
+            cause: EvalError::Overflow{ op: Operator::Inc, left: num(i64::MAX), right: num(2) },
+        }));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let tests = vec![
+            ("5 inc 1 if a > 1", 1, "cannot assign to a number, expecting register name"),
+            ("true set 1 if a > 1", 1, "cannot assign to a boolean, expecting register name"),
+            ("a inc b > 1 if true", 7, "value must be a number"),
+            ("a inc 1 if b + 1", 12, "condition must be a boolean"),
+            ("a inc 1 if b + 1 and c > 2", 18, "operands of logical operator must be booleans"),
+            ("a inc 1 if b == true", 14, "operands of comparison must have the same type"),
+            ("a inc 1 if (b < 2) < 3", 20, "operands of comparison must be numbers"),
+            ("a inc 2 * (b < 2) if true", 9, "operands of arithmetic must be numbers"),
+            ("a inc -true if true", 7, "operand of '-' must be a number"),
+            ("a inc 1 if not b", 12, "operand of 'not' must be a boolean"),
+            ("a add 1 if b > 1", 3, "expecting one of inc, dec, set, mul, div, or mod"),
+            ("a inc 1 when b > 1", 9, "expecting 'if' or operator"),
+            ("a inc 1 if b > 1 c", 18, "expecting end of statement or operator"),
+            ("a inc (1 + 2 if b", 14, "expecting ')'"),
+            ("a inc 1 if b >", 15, "unexpected end of statement, expecting operand"),
+            ("a inc 1 if b $ 2", 14, "unexpected character"),
+            ("a inc 1 if if", 12, "unexpected keyword, expecting operand"),
+        ];
+
+        for t in tests {
+            let err = t.0.parse::<Statement>().unwrap_err();
+            assert_eq!((err.column, err.msg), (t.1, t.2), "input = {:?}", t.0);
+        }
+    }
+}
+
 const PUZZLE: &'static str = r"I Heard You Like Registers";
 const INPUT: &'static str = r"
 gug dec 188 if zpw >= 8
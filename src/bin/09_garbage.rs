@@ -77,74 +77,156 @@ How many non-canceled characters are within the garbage in your puzzle input?
 */
 
 extern crate aoc;
+extern crate clap;
 
+use aoc::tree;
+use clap::Arg;
+use std::ascii;
 use std::fmt;
 use std::error;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::rc::Rc;
 use std::str;
+use std::cell::Ref;
 
 fn main() {
-    let mut input = aoc::ProgramInput::new(PUZZLE, INPUT);
+    let mut input = aoc::ProgramInput::with_args(PUZZLE, INPUT, vec![
+        Arg::with_name("diagnose")
+            .long("diagnose")
+            .help("Report all problems in the stream instead of stopping at the first"),
+        Arg::with_name("stats")
+            .long("stats")
+            .help("Print statistics about the structure of the stream"),
+        Arg::with_name("pretty")
+            .long("pretty")
+            .help("Print the stream with one chunk per line, indented by depth"),
+    ]);
     println!("Day 9: {}", PUZZLE);
 
-    let tok: Token = input.to_str().parse().unwrap();
-    println!(":: Answer 1 is {}", tok.score());
-    println!(":: Answer 2 is {}", tok.noncanceled_garbage());
+    if input.is_present("diagnose") {
+        let (_, errors) = Token::diagnose(input.to_str());
+        for e in &errors {
+            println!("{}", e);
+        }
+        println!(":: Found {} problems", errors.len());
+        return;
+    }
+
+    // The statistics include the totals, so a single pass over the
+    // stream yields the answers, the statistics and the pretty output.
+    let show_stats = input.is_present("stats");
+    let mut pretty = if input.is_present("pretty") { Some(Pretty::new()) } else { None };
+    let s = input.to_str();
+    let mut stats = StreamStats::default();
+    let mut stream = Stream::new(s.as_bytes());
+    while let Some(ev) = stream.next() {
+        let ev = match ev {
+            Ok(ev) => ev,
+            Err(e) => {
+                println!(":: Error: {}", e);
+                return;
+            }
+        };
+        stats.record(ev, stream.depth());
+        if let Some(ref mut p) = pretty {
+            p.push(s, ev, stream.offset());
+        }
+    }
+
+    if let Some(p) = pretty {
+        print!("{}", p.finish());
+    }
+    if show_stats {
+        print!("{}", stats);
+    }
+    println!(":: Answer 1 is {}", stats.totals.score);
+    println!(":: Answer 2 is {}", stats.totals.garbage);
 }
 
+/// A chunk of the stream, which is either a group or garbage.
 #[derive(Debug,PartialEq)]
-pub enum Token {
+pub enum Chunk {
     Group(Group),
     Garbage(Garbage),
 }
 
+impl Chunk {
+    /// Returns the byte range of the chunk in the input, including its delimiters.
+    pub fn span(&self) -> Range<usize> {
+        match *self {
+            Chunk::Group(ref g) => g.span.clone(),
+            Chunk::Garbage(ref g) => g.span.clone(),
+        }
+    }
+}
+
+/// A handle to a chunk in the stream, together with the chunks it contains.
+#[derive(Debug,PartialEq,Clone)]
+pub struct Token {
+    node: tree::Node<Chunk>,
+    source: Rc<str>,
+}
+
 impl Token {
-    pub fn group(self) -> Option<Group> {
-        match self {
-            Token::Group(g) => Some(g),
+    pub fn group(&self) -> Option<Ref<'_, Group>> {
+        Ref::filter_map(self.node.data(), |c| match *c {
+            Chunk::Group(ref g) => Some(g),
             _ => None,
-        }
+        }).ok()
     }
 
-    pub fn garbage(self) -> Option<Garbage> {
-        match self {
-            Token::Garbage(g) => Some(g),
+    pub fn garbage(&self) -> Option<Ref<'_, Garbage>> {
+        Ref::filter_map(self.node.data(), |c| match *c {
+            Chunk::Garbage(ref g) => Some(g),
             _ => None,
-        }
+        }).ok()
     }
 
-    pub fn as_str(&self) -> &str {
-        match *self {
-            Token::Group(ref g) => g.data.as_str(),
-            Token::Garbage(ref g) => g.data.as_str(),
-        }
+    /// Returns the tokens directly contained in this group.
+    pub fn children(&self) -> impl Iterator<Item = Token> {
+        let source = self.source.clone();
+        self.node.children().map(move |node| Token{ node, source: source.clone() })
     }
 
-    pub fn score(&self) -> usize {
-        self._score(1)
+    /// Returns the byte range of the token in the input.
+    pub fn span(&self) -> Range<usize> {
+        self.node.data().span()
     }
 
-    fn _score(&self, depth: usize) -> usize {
-        match *self {
-            Token::Group(ref g) => g.children.iter().fold(depth, |acc, x| acc + x._score(depth + 1)),
-            Token::Garbage(_) => 0,
-        }
+    /// Returns the exact text of the token in the input.
+    pub fn as_str(&self) -> &str {
+        &self.source[self.span()]
+    }
+
+    pub fn score(&self) -> usize {
+        // Every group scores one for itself and one for every group it
+        // is nested in, so we fold the pair (score, number of groups).
+        self.node.fold(|c, xs: Vec<(usize, usize)>| match *c {
+            Chunk::Group(_) => xs.iter().fold((1, 1), |acc, x| (acc.0 + x.0 + x.1, acc.1 + x.1)),
+            Chunk::Garbage(_) => (0, 0),
+        }).0
     }
 
     pub fn noncanceled_garbage(&self) -> usize {
-        match *self {
-            Token::Group(ref g) => g.children.iter().fold(0, |acc, x| acc + x.noncanceled_garbage()),
-            Token::Garbage(ref g) => g.noncanceled,
-        }
+        self.node.fold(|c, xs: Vec<usize>| match *c {
+            Chunk::Group(_) => xs.iter().sum(),
+            Chunk::Garbage(ref g) => g.noncanceled,
+        })
     }
 
-    fn parse(mut chars: &mut str::CharIndices) -> Result<Self, ParseError> {
-        let data = chars.as_str();
-        match chars.next() {
-            Some((n, '{')) => Group::parse(&mut chars, n).map(|g| Token::Group(g)),
-            Some((n, '<')) => Garbage::parse(&mut chars, n).map(|g| Token::Garbage(g)),
-            Some((n, _)) => Err(ParseError::with_str(data, n, "unexpected character, expecting one of '{' or '<'")),
-            _ => Err(ParseError::with_str(data, data.len(), "unexpected end-of-file")),
-        }
+    /// Parses the stream without stopping at the first problem.
+    ///
+    /// After an unexpected character, the parser skips ahead to the next
+    /// `,` or `}` and carries on. Groups and garbage that are still open
+    /// at the end of the input are closed there. Anything after the first
+    /// chunk is reported, and further chunks are checked as well. Returns
+    /// the first chunk as token, if there is one, together with all problems
+    /// in the order found.
+    pub fn diagnose(s: &str) -> (Option<Token>, Vec<ParseError>) {
+        let mut p = Parser::new(s, true);
+        let node = p.stream().expect("lenient parser records errors");
+        (node.map(|node| Token{ node, source: Rc::from(s) }), p.errors)
     }
 }
 
@@ -152,130 +234,686 @@ impl str::FromStr for Token {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut chars = s.char_indices();
-        Token::parse(&mut chars)
+        let node = Parser::new(s, false).stream()?;
+        Ok(Token{ node: node.expect("strict parser fails on errors"), source: Rc::from(s) })
     }
 }
 
 #[derive(Debug,PartialEq)]
 pub struct Group {
-    data: String,
-    children: Vec<Token>,
+    span: Range<usize>,
 }
 
-impl Group {
-    fn parse(mut chars: &mut str::CharIndices, start: usize) -> Result<Self, ParseError> {
-        let mut group = Self{
-            data: String::new(),
-            children: Vec::new(),
+#[derive(Debug,PartialEq)]
+pub struct Garbage {
+    span: Range<usize>,
+    noncanceled: usize,
+}
+
+/// A parser that builds the tree of chunks, either failing at the first
+/// problem or recording it and recovering.
+struct Parser<'a> {
+    s: &'a str,
+    chars: str::CharIndices<'a>,
+    recover: bool,
+    errors: Vec<ParseError>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str, recover: bool) -> Self {
+        Parser {
+            s,
+            chars: s.char_indices(),
+            recover,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Reports a problem at a byte offset, which is only fatal if
+    /// the parser does not recover.
+    fn error(&mut self, offset: usize, msg: &'static str) -> Result<(), ParseError> {
+        let err = ParseError::at(self.s, offset, msg);
+        if self.recover {
+            self.errors.push(err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Parses the outermost chunk, which has to start at the beginning.
+    fn stream(&mut self) -> Result<Option<tree::Node<Chunk>>, ParseError> {
+        let mut first = match self.chars.next() {
+            Some((n, c)) if c == '{' || c == '<' => Some(self.chunk(n, c)?),
+            Some((n, _)) => {
+                self.error(n, "unexpected character, expecting one of '{' or '<'")?;
+                None
+            }
+            None => {
+                let n = self.s.len();
+                self.error(n, "unexpected end-of-file")?;
+                return Ok(None);
+            }
         };
-        let data = chars.as_str();
-        let mut me = String::from("{");
-        while let Some((n, c)) = chars.next() {
-            match c {
-                '{' => {
-                    let tok = Group::parse(&mut chars, n)?;
-                    group.children.push(Token::Group(tok));
+
+        // Only whitespace may follow. When recovering, every further chunk
+        // is parsed as well, so that its own problems are reported too.
+        loop {
+            let (n, c) = match self.chars.find(|&(_, c)| !c.is_whitespace()) {
+                Some(x) => x,
+                None => return Ok(first),
+            };
+            if first.is_some() {
+                self.error(n, "unexpected character after the end of the stream")?;
+            }
+            let (n, c) = if c == '{' || c == '<' {
+                (n, c)
+            } else {
+                match self.chars.find(|&(_, c)| c == '{' || c == '<') {
+                    Some(x) => x,
+                    None => return Ok(first),
                 }
+            };
+            let node = self.chunk(n, c)?;
+            first.get_or_insert(node);
+        }
+    }
 
-                '<' => {
-                    let tok = Garbage::parse(&mut chars, n)?;
-                    group.children.push(Token::Garbage(tok));
+    /// Parses a group or garbage, given its first character at byte `start`.
+    ///
+    /// Open groups are kept on an explicit stack instead of the call stack,
+    /// so that deep nesting cannot overflow it. Every group is appended to
+    /// its parent only once it is closed, while the parent is still detached.
+    fn chunk(&mut self, start: usize, c: char) -> Result<tree::Node<Chunk>, ParseError> {
+        if c == '<' {
+            return self.garbage(start);
+        }
+
+        // Every open group, with the offset of its '{' and what it accepts next.
+        let mut stack = vec![(Parser::open_group(start), start, Expect::ChunkOrEnd)];
+        while let Some((n, c)) = self.chars.next() {
+            let expect = stack.last().expect("a group is open").2;
+            let close = match c {
+                '{' | '<' => {
+                    if expect == Expect::Separator {
+                        // Carry on as if the comma was there.
+                        self.error(n, expect.message())?;
+                    }
+                    stack.last_mut().expect("a group is open").2 = Expect::Separator;
+                    if c == '{' {
+                        stack.push((Parser::open_group(n), n, Expect::ChunkOrEnd));
+                    } else {
+                        let child = self.garbage(n)?;
+                        stack.last_mut().expect("a group is open").0.append(child);
+                    }
+                    None
                 }
 
-                ',' => {}
+                ',' => {
+                    if expect != Expect::Separator {
+                        self.error(n, expect.message())?;
+                    }
+                    stack.last_mut().expect("a group is open").2 = Expect::Chunk;
+                    None
+                }
 
                 '}' => {
-                    me.push_str(&data[..(n-start)]); // FIXME!
-                    group.data = me;
-                    return Ok(group);
+                    if expect == Expect::Chunk {
+                        self.error(n, expect.message())?;
+                    }
+                    Some(n + 1)
                 }
 
                 _ => {
-                    me.push_str(data);
-                    return Err(ParseError::with(me, n, "unexpected character, expecting one of '{', '<', ',', or '}'"));
+                    self.error(n, "unexpected character, expecting one of '{', '<', ',', or '}'")?;
+                    match self.chars.find(|&(_, c)| c == ',' || c == '}') {
+                        Some((n, '}')) => Some(n + 1),
+                        Some(_) => {
+                            stack.last_mut().expect("a group is open").2 = Expect::Chunk;
+                            None
+                        }
+                        None => break,
+                    }
+                }
+            };
+
+            if let Some(end) = close {
+                let (node, start, _) = stack.pop().expect("a group is open");
+                *node.data_mut() = Chunk::Group(Group{ span: start..end });
+                match stack.last_mut() {
+                    Some(parent) => parent.0.append(node),
+                    None => return Ok(node),
                 }
             }
         }
-        me.push_str(data);
-        Err(ParseError::with(me, data.len(), "unexpected end-of-file"))
+
+        // The input ended with groups still open, so close them there,
+        // innermost first.
+        let mut inner: Option<tree::Node<Chunk>> = None;
+        while let Some((mut node, start, _)) = stack.pop() {
+            self.error(start, "unexpected end-of-file, group is not closed")?;
+            *node.data_mut() = Chunk::Group(Group{ span: start..self.s.len() });
+            if let Some(child) = inner {
+                node.append(child);
+            }
+            inner = Some(node);
+        }
+        Ok(inner.expect("the outermost group was open"))
     }
-}
 
-#[derive(Debug,PartialEq)]
-pub struct Garbage {
-    data: String,
-    noncanceled: usize,
-}
+    fn open_group(start: usize) -> tree::Node<Chunk> {
+        tree::Node::new(Chunk::Group(Group{ span: start..start }))
+    }
 
-impl Garbage {
-    fn parse(chars: &mut str::CharIndices, start: usize) -> Result<Self, ParseError> {
-        let data = chars.as_str();
-        let mut me = String::from("<");
-        let mut uncanceled = 0;
-        while let Some((n, c)) = chars.next() {
+    fn garbage(&mut self, start: usize) -> Result<tree::Node<Chunk>, ParseError> {
+        let mut noncanceled = 0;
+        let mut end = None;
+        while let Some((n, c)) = self.chars.next() {
             match c {
                 '!' => {
-                    if chars.next().is_none() {
-                        break;
-                    }
+                    self.chars.next();
                 }
 
                 '>' => {
-                    me.push_str(&data[..(n-start)]); // FIXME!
-                    return Ok(Self{
-                        data: me,
-                        noncanceled: uncanceled,
-                    });
+                    end = Some(n + 1);
+                    break;
                 }
 
                 _ => {
-                    uncanceled += 1;
+                    noncanceled += 1;
                 }
             }
         }
-        me.push_str(data);
-        Err(ParseError::with(me, data.len(), "unexpected end-of-file"))
+        let end = match end {
+            Some(end) => end,
+            None => {
+                self.error(start, "unexpected end-of-file, garbage is not terminated")?;
+                self.s.len()
+            }
+        };
+        Ok(tree::Node::new(Chunk::Garbage(Garbage{
+            span: start..end,
+            noncanceled,
+        })))
+    }
+}
+
+/// An event emitted by `Stream` while scanning the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A group was opened with `{`.
+    GroupStart,
+
+    /// The most recently opened group was closed with `}`.
+    GroupEnd,
+
+    /// A complete block of garbage, where `len` is the number of
+    /// non-canceled characters and `canceled` the number of `!`
+    /// that canceled the character following them.
+    Garbage{ len: usize, canceled: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Groups,
+    Garbage{ len: usize, canceled: usize },
+    Cancel{ len: usize, canceled: usize },
+    Done,
+}
+
+/// What the scanner accepts next outside of garbage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    /// A group or garbage, as at the start of the stream and after a comma.
+    Chunk,
+    /// A group, garbage or `}`, right after a group was opened.
+    ChunkOrEnd,
+    /// A comma or `}` after a chunk, or nothing but whitespace once the
+    /// outermost chunk is complete.
+    Separator,
+}
+
+impl Expect {
+    /// Describes the characters that were expected inside a group.
+    fn message(self) -> &'static str {
+        match self {
+            Expect::Chunk => "unexpected character, expecting one of '{' or '<'",
+            Expect::ChunkOrEnd => "unexpected character, expecting one of '{', '<', or '}'",
+            Expect::Separator => "unexpected character, expecting one of ',' or '}'",
+        }
+    }
+}
+
+const CHUNK_SIZE: usize = 8192;
+
+/// An iterative scanner that reads a stream in chunks and emits events.
+///
+/// Memory usage does not depend on the size or nesting of the input:
+/// only the current depth is kept, not the groups themselves.
+///
+/// Like the `Token` parser, the scanner accepts exactly one outermost chunk
+/// and requires commas between the chunks of a group, but no others. Unlike
+/// the parser, it also allows whitespace before the outermost chunk.
+pub struct Stream<R> {
+    reader: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    offset: usize,
+    depth: usize,
+    state: State,
+    expect: Expect,
+}
+
+impl<R: Read> Stream<R> {
+    pub fn new(reader: R) -> Self {
+        Stream {
+            reader,
+            buf: vec![0; CHUNK_SIZE].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            offset: 0,
+            depth: 0,
+            state: State::Groups,
+            expect: Expect::Chunk,
+        }
+    }
+
+    /// Returns the number of groups that are currently open.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        while self.pos == self.end {
+            match self.reader.read(&mut self.buf) {
+                Ok(0) => return Ok(None),
+                Ok(n) => {
+                    self.pos = 0;
+                    self.end = n;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let b = self.buf[self.pos];
+        self.pos += 1;
+        self.offset += 1;
+        Ok(Some(b))
+    }
+
+    fn fail(&mut self, err: StreamError) -> Option<Result<Event, StreamError>> {
+        self.state = State::Done;
+        Some(Err(err))
+    }
+}
+
+impl<R: Read> Iterator for Stream<R> {
+    type Item = Result<Event, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.state == State::Done {
+                return None;
+            }
+            let b = match self.next_byte() {
+                Ok(Some(b)) => b,
+                Ok(None) => {
+                    let in_garbage = self.state != State::Groups;
+                    if in_garbage || self.depth > 0 {
+                        let err = StreamError::UnexpectedEof{ offset: self.offset, depth: self.depth, in_garbage };
+                        return self.fail(err);
+                    }
+                    if self.expect == Expect::Chunk {
+                        return self.fail(StreamError::Empty);
+                    }
+                    self.state = State::Done;
+                    return None;
+                }
+                Err(e) => return self.fail(StreamError::Io(e)),
+            };
+
+            let chunk = self.expect != Expect::Separator;
+            self.state = match (self.state, b) {
+                (State::Groups, b'{') if chunk => {
+                    self.depth += 1;
+                    self.expect = Expect::ChunkOrEnd;
+                    return Some(Ok(Event::GroupStart));
+                }
+                (State::Groups, b'}') if self.depth > 0 && self.expect != Expect::Chunk => {
+                    self.depth -= 1;
+                    self.expect = Expect::Separator;
+                    return Some(Ok(Event::GroupEnd));
+                }
+                (State::Groups, b'<') if chunk => State::Garbage{ len: 0, canceled: 0 },
+                (State::Groups, b',') if self.depth > 0 && !chunk => {
+                    self.expect = Expect::Chunk;
+                    State::Groups
+                }
+                (State::Groups, b) if self.depth == 0 && b.is_ascii_whitespace() => State::Groups,
+                (State::Groups, b) => {
+                    let err = StreamError::Unexpected{ offset: self.offset - 1, found: b };
+                    return self.fail(err);
+                }
+                (State::Garbage{ len, canceled }, b'>') => {
+                    self.state = State::Groups;
+                    self.expect = Expect::Separator;
+                    return Some(Ok(Event::Garbage{ len, canceled }));
+                }
+                (State::Garbage{ len, canceled }, b'!') => State::Cancel{ len, canceled: canceled + 1 },
+                // Only the first byte of each UTF-8 character is counted.
+                (State::Garbage{ len, canceled }, b) if b & 0xC0 != 0x80 => State::Garbage{ len: len + 1, canceled },
+                (State::Garbage{ len, canceled }, _) => State::Garbage{ len, canceled },
+                (State::Cancel{ len, canceled }, _) => State::Garbage{ len, canceled },
+                (State::Done, _) => unreachable!(),
+            };
+        }
+    }
+}
+
+/// A summary of the structure of a stream, as computed by `StreamStats::scan`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StreamStats {
+    pub totals: Totals,
+
+    /// Number of groups at each depth, starting with the outermost at depth 1.
+    pub groups_per_depth: Vec<usize>,
+
+    /// Number of garbage blocks.
+    pub garbage_blocks: usize,
+
+    /// Number of characters canceled by `!`.
+    pub canceled: usize,
+
+    /// Number of non-canceled characters in the largest garbage block.
+    pub largest_garbage: usize,
+}
+
+impl StreamStats {
+    /// Collects the statistics in a single pass over the stream.
+    pub fn scan<R: Read>(reader: R) -> Result<Self, StreamError> {
+        let mut stats = StreamStats::default();
+        let mut stream = Stream::new(reader);
+        while let Some(ev) = stream.next() {
+            stats.record(ev?, stream.depth());
+        }
+        Ok(stats)
+    }
+
+    /// Adds an event to the statistics, where `depth` is the depth of the
+    /// stream right after the event.
+    pub fn record(&mut self, ev: Event, depth: usize) {
+        match ev {
+            Event::GroupStart => {
+                if self.groups_per_depth.len() < depth {
+                    self.groups_per_depth.push(0);
+                }
+                self.groups_per_depth[depth - 1] += 1;
+                self.totals.groups += 1;
+                self.totals.score += depth;
+            }
+            Event::GroupEnd => {}
+            Event::Garbage{ len, canceled } => {
+                self.garbage_blocks += 1;
+                self.canceled += canceled;
+                self.largest_garbage = self.largest_garbage.max(len);
+                self.totals.garbage += len;
+            }
+        }
+    }
+
+    /// Returns the deepest nesting of groups, where the outermost group is 1.
+    pub fn max_depth(&self) -> usize {
+        self.groups_per_depth.len()
+    }
+}
+
+impl fmt::Display for StreamStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "groups: {} (score {})", self.totals.groups, self.totals.score)?;
+        writeln!(f, "max depth: {}", self.max_depth())?;
+        for (i, n) in self.groups_per_depth.iter().enumerate() {
+            writeln!(f, "  depth {}: {} groups", i + 1, n)?;
+        }
+        writeln!(f, "garbage blocks: {} ({} characters)", self.garbage_blocks, self.totals.garbage)?;
+        writeln!(f, "largest garbage block: {} characters", self.largest_garbage)?;
+        writeln!(f, "canceled characters: {}", self.canceled)
+    }
+}
+
+/// Re-indents a stream from its events, with every chunk on its own line:
+///
+/// ```text
+/// {
+///   <a>,
+///   {
+///     {}
+///   }
+/// }
+/// ```
+///
+/// Only the current depth is kept, so deep nesting is no problem. Since an
+/// event does not say whether a group is empty or whether another chunk
+/// follows, the opening brace and the separator are written one event late.
+#[derive(Debug, Default)]
+pub struct Pretty {
+    out: String,
+    depth: usize,
+    /// A group was opened, but nothing was written for it yet.
+    open: bool,
+    /// A chunk was completed, but its separator was not written yet.
+    pending: bool,
+    /// The offset right after the previous event.
+    last: usize,
+}
+
+impl Pretty {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an event from scanning `source`, where `offset` is the offset
+    /// of the stream right after the event.
+    pub fn push(&mut self, source: &str, ev: Event, offset: usize) {
+        match ev {
+            Event::GroupStart => {
+                self.begin_chunk();
+                self.depth += 1;
+                self.open = true;
+            }
+            Event::GroupEnd => {
+                self.indent(self.depth - 1);
+                if self.open {
+                    self.out.push_str("{}");
+                    self.open = false;
+                } else {
+                    self.out.push('}');
+                }
+                self.depth -= 1;
+                self.pending = true;
+            }
+            Event::Garbage{ .. } => {
+                self.begin_chunk();
+                self.indent(self.depth);
+                // Only separators and whitespace come between the previous
+                // event and the garbage, so its text starts at the next '<'.
+                let start = self.last + source[self.last..offset].find('<').expect("garbage starts with '<'");
+                self.out.push_str(&source[start..offset]);
+                self.pending = true;
+            }
+        }
+        self.last = offset;
+    }
+
+    /// Returns the text written so far, ending with a newline.
+    pub fn finish(mut self) -> String {
+        if self.pending {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    /// Writes what was held back before another chunk starts.
+    fn begin_chunk(&mut self) {
+        if self.open {
+            self.indent(self.depth - 1);
+            self.out.push_str("{\n");
+            self.open = false;
+        } else if self.pending {
+            self.out.push_str(",\n");
+            self.pending = false;
+        }
+    }
+
+    fn indent(&mut self, depth: usize) {
+        if self.pending && !self.open {
+            // The previous chunk was the last one of the group being closed.
+            self.out.push('\n');
+            self.pending = false;
+        }
+        self.out.push_str(&"  ".repeat(depth));
+    }
+}
+
+/// Pretty-prints a stream in a single pass, see `Pretty`.
+pub fn pretty(s: &str) -> Result<String, StreamError> {
+    let mut p = Pretty::new();
+    let mut stream = Stream::new(s.as_bytes());
+    while let Some(ev) = stream.next() {
+        p.push(s, ev?, stream.offset());
+    }
+    Ok(p.finish())
+}
+
+/// The totals of a stream, as computed by `Totals::scan`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Totals {
+    pub groups: usize,
+    pub score: usize,
+    pub garbage: usize,
+}
+
+impl Totals {
+    /// Computes the totals in a single pass over the stream.
+    pub fn scan<R: Read>(reader: R) -> Result<Self, StreamError> {
+        let mut totals = Totals::default();
+        let mut stream = Stream::new(reader);
+        while let Some(ev) = stream.next() {
+            match ev? {
+                Event::GroupStart => {
+                    totals.groups += 1;
+                    totals.score += stream.depth();
+                }
+                Event::GroupEnd => {}
+                Event::Garbage{ len, .. } => totals.garbage += len,
+            }
+        }
+        Ok(totals)
     }
 }
 
 #[derive(Debug)]
-pub struct ParseError {
-    msg: &'static str,
-    data: String,
-    col: usize,
+pub enum StreamError {
+    /// Error due to the underlying reader failing.
+    Io(io::Error),
+
+    /// Error due to a byte that is not allowed at a 0-based byte offset.
+    Unexpected{
+        offset: usize,
+        found: u8,
+    },
+
+    /// Error due to the stream ending inside a group or garbage.
+    UnexpectedEof{
+        offset: usize,
+        depth: usize,
+        in_garbage: bool,
+    },
+
+    /// Error due to the stream not containing any group or garbage.
+    Empty,
 }
 
-impl ParseError {
-    #[allow(unused)]
-    fn new(data: &str, msg: &'static str) -> Self {
-        Self {
-            msg: msg,
-            data: String::from(data),
-            col: 0,
+impl error::Error for StreamError {
+    fn description(&self) -> &str {
+        match *self {
+            StreamError::Io(..) => "cannot read stream",
+            StreamError::Unexpected{..} => "unexpected character",
+            StreamError::UnexpectedEof{..} => "unexpected end-of-file",
+            StreamError::Empty => "empty stream",
         }
     }
+}
 
-    fn with(data: String, col: usize, msg: &'static str) -> Self {
-        Self {
-            msg: msg,
-            data: data,
-            col: col,
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StreamError::Io(ref e) => write!(f, "cannot read stream: {}", e),
+            StreamError::Unexpected{ offset, found } => {
+                write!(f, "unexpected character '{}' at byte {}", ascii::escape_default(found), offset)
+            }
+            StreamError::UnexpectedEof{ offset, depth, in_garbage } => {
+                let inside = if in_garbage { "garbage" } else { "group" };
+                write!(f, "unexpected end-of-file at byte {} inside {}, with {} groups open", offset, inside, depth)
+            }
+            StreamError::Empty => write!(f, "empty stream, expecting a group or garbage"),
         }
     }
+}
+
+/// Maximum number of characters shown on either side of an error.
+const SNIPPET_WIDTH: usize = 30;
+
+/// Error due to a malformed stream, at a 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    msg: &'static str,
+    line: usize,
+    column: usize,
+    snippet: String,
+    caret: usize,
+}
+
+impl ParseError {
+    /// Creates an error at byte `offset` of `s`, with an excerpt of the line it is on.
+    fn at(s: &str, offset: usize, msg: &'static str) -> Self {
+        let start = s[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = s[offset..].find('\n').map_or(s.len(), |i| offset + i);
+        let line: Vec<char> = s[start..end].chars().collect();
+        let column = s[start..offset].chars().count() + 1;
 
-    fn with_str(data: &str, col: usize, msg: &'static str) -> Self {
-        Self {
-            msg: msg,
-            data: String::from(data),
-            col: col,
+        let from = (column - 1).saturating_sub(SNIPPET_WIDTH);
+        let to = line.len().min(column + SNIPPET_WIDTH);
+        let mut snippet = String::new();
+        if from > 0 {
+            snippet.push_str("...");
+        }
+        let caret = snippet.len() + column - 1 - from;
+        snippet.extend(&line[from..to]);
+        if to < line.len() {
+            snippet.push_str("...");
+        }
+
+        ParseError {
+            msg,
+            line: s[..offset].matches('\n').count() + 1,
+            column,
+            snippet,
+            caret,
         }
     }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: at column {} in: {}", self.msg, self.col, self.data)
+        writeln!(f, "{}: at line {}, column {}", self.msg, self.line, self.column)?;
+        writeln!(f, "    {}", self.snippet)?;
+        write!(f, "    {:1$}^", "", self.caret)
     }
 }
 
@@ -305,6 +943,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_nested_spans() {
+        let input = "{{<a!>>,{}},<{}>,{<x>,{<!!>}}}";
+        let tok: Token = input.parse().unwrap();
+        assert_eq!(tok.span(), 0..input.len());
+        assert_eq!(tok.as_str(), input);
+
+        let texts = |t: &Token| t.children().map(|x| String::from(x.as_str())).collect::<Vec<_>>();
+        let children: Vec<Token> = tok.children().collect();
+        assert_eq!(texts(&tok), vec!["{<a!>>,{}}", "<{}>", "{<x>,{<!!>}}"]);
+        assert_eq!(texts(&children[0]), vec!["<a!>>", "{}"]);
+        assert_eq!(texts(&children[2]), vec!["<x>", "{<!!>}"]);
+
+        let deepest = children[2].children().nth(1).unwrap().children().next().unwrap();
+        assert_eq!(deepest.as_str(), "<!!>");
+        assert_eq!(deepest.span(), 23..27);
+        assert!(deepest.garbage().is_some());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let tests = vec![
+            ("{{<a>x}}", (1, 6), "unexpected character, expecting one of '{', '<', ',', or '}'"),
+            ("x", (1, 1), "unexpected character, expecting one of '{' or '<'"),
+            ("{}\n{}", (2, 1), "unexpected character after the end of the stream"),
+            ("{{},{<ab}", (1, 6), "unexpected end-of-file, garbage is not terminated"),
+            ("{{}", (1, 1), "unexpected end-of-file, group is not closed"),
+            ("{}{}", (1, 3), "unexpected character after the end of the stream"),
+            ("{{}{}}", (1, 4), "unexpected character, expecting one of ',' or '}'"),
+            ("{<a>,}", (1, 6), "unexpected character, expecting one of '{' or '<'"),
+            ("{,<a>}", (1, 2), "unexpected character, expecting one of '{', '<', or '}'"),
+            ("", (1, 1), "unexpected end-of-file"),
+        ];
+
+        for t in tests {
+            let err = t.0.parse::<Token>().unwrap_err();
+            assert_eq!((err.line, err.column, err.msg), ((t.1).0, (t.1).1, t.2), "input = {:?}", t.0);
+        }
+
+        let err = "{{<a>x}}".parse::<Token>().unwrap_err();
+        assert_eq!(err.to_string(), "unexpected character, expecting one of '{', '<', ',', or '}': at line 1, column 6
+    {{<a>x}}
+         ^");
+        let long = format!("{{{}x}}", "{},".repeat(20));
+        let err = long.parse::<Token>().unwrap_err();
+        assert_eq!(err.snippet, format!("...{}x}}", "{},".repeat(10)));
+        assert_eq!(&err.snippet[err.caret..err.caret + 1], "x");
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let (tok, errors) = Token::diagnose("{{a,<x>},{b}},c}\n{<!>");
+        let found: Vec<(usize, usize, &str)> = errors.iter().map(|e| (e.line, e.column, e.msg)).collect();
+        assert_eq!(found, vec![
+            (1, 3, "unexpected character, expecting one of '{', '<', ',', or '}'"),
+            (1, 11, "unexpected character, expecting one of '{', '<', ',', or '}'"),
+            (1, 14, "unexpected character after the end of the stream"),
+            (2, 2, "unexpected end-of-file, garbage is not terminated"),
+            (2, 1, "unexpected end-of-file, group is not closed"),
+        ]);
+        let tok = tok.unwrap();
+        assert_eq!(tok.as_str(), "{{a,<x>},{b}}");
+        assert_eq!(tok.noncanceled_garbage(), 1);
+
+        let (tok, errors) = Token::diagnose("{{<ab},{");
+        let msgs: Vec<&str> = errors.iter().map(|e| e.msg).collect();
+        assert_eq!(msgs, vec![
+            "unexpected end-of-file, garbage is not terminated",
+            "unexpected end-of-file, group is not closed",
+            "unexpected end-of-file, group is not closed",
+        ]);
+        assert_eq!(tok.unwrap().children().next().unwrap().as_str(), "{<ab},{");
+
+        let (tok, errors) = Token::diagnose("x{}, {x}\n<");
+        let found: Vec<(usize, usize)> = errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(found, vec![(1, 1), (1, 4), (1, 7), (2, 1), (2, 1)]);
+        assert_eq!(tok.unwrap().as_str(), "{}");
+    }
+
+    #[test]
+    fn test_parse_deep() {
+        let n = 200_000;
+        let s = format!("{}<a>{}", "{".repeat(n), "}".repeat(n));
+        let tok: Token = s.parse().unwrap();
+        assert_eq!(tok.noncanceled_garbage(), 1);
+
+        let (tok, errors) = Token::diagnose(&s[..s.len() - 1]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (1, 1));
+        assert_eq!(tok.unwrap().span(), 0..s.len() - 1);
+    }
+
     #[test]
     fn test_uncanceled_garbage() {
         let tests = vec![
@@ -341,6 +1071,120 @@ mod tests {
             assert_eq!(tok.score(), t.1);
         }
     }
+
+    /// A reader that returns at most one byte per call.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let tests = vec![
+            ("{}", 1, 0),
+            ("{{<!>},{<!>},{<!>},{<a>}}", 3, 13),
+            ("{{<ab>},{<ab>},{<ab>},{<ab>}}", 9, 8),
+            ("{<{o\"i!a,<{i<a>,<!!!>>,{<é>}}\n", 3, 11),
+            ("\n {{},{}} \n", 5, 0),
+            ("<a>", 0, 1),
+        ];
+
+        for t in tests {
+            let totals = Totals::scan(Trickle(t.0.as_bytes())).unwrap();
+            assert_eq!((totals.score, totals.garbage), (t.1, t.2), "input = {:?}", t.0);
+        }
+
+        let events: Vec<Event> = Stream::new("{<a!b>,{}}".as_bytes()).map(|x| x.unwrap()).collect();
+        assert_eq!(events, vec![
+            Event::GroupStart,
+            Event::Garbage{ len: 1, canceled: 1 },
+            Event::GroupStart,
+            Event::GroupEnd,
+            Event::GroupEnd,
+        ]);
+    }
+
+    #[test]
+    fn test_stream_stats() {
+        let stats = StreamStats::scan("{{<a!>>,{}},<!!{{x>,{<xyz!!>,{<>}}}".as_bytes()).unwrap();
+        assert_eq!(stats.groups_per_depth, vec![1, 2, 2]);
+        assert_eq!(stats.max_depth(), 3);
+        assert_eq!(stats.garbage_blocks, 4);
+        assert_eq!(stats.canceled, 3);
+        assert_eq!(stats.largest_garbage, 3);
+        assert_eq!(stats.totals, Totals{ groups: 5, score: 11, garbage: 7 });
+    }
+
+    #[test]
+    fn test_pretty() {
+        let tests = vec![
+            ("{}", "{}\n"),
+            ("<a,{b}>", "<a,{b}>\n"),
+            (" {{},<>}\n", "{\n  {},\n  <>\n}\n"),
+            ("{{<a!>>,{}},<{}>,{{}}}", "{
+  {
+    <a!>>,
+    {}
+  },
+  <{}>,
+  {
+    {}
+  }
+}
+"),
+        ];
+
+        for t in tests {
+            assert_eq!(pretty(t.0).unwrap(), t.1, "input = {:?}", t.0);
+        }
+
+        // The output grows quadratically with the depth, due to indentation.
+        let n = 5_000;
+        let deep = pretty(&format!("{}<>{}", "{".repeat(n), "}".repeat(n))).unwrap();
+        assert_eq!(deep.lines().count(), 2 * n + 1);
+        assert_eq!(deep.lines().nth(n), Some(&*format!("{}<>", "  ".repeat(n))));
+    }
+
+    #[test]
+    fn test_stream_deep() {
+        let n = 1_000_000;
+        let s = format!("{}{}", "{".repeat(n), "}".repeat(n));
+        let totals = Totals::scan(s.as_bytes()).unwrap();
+        assert_eq!(totals.groups, n);
+        assert_eq!(totals.score, n * (n + 1) / 2);
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let tests = vec![
+            ("{a}", "unexpected character 'a' at byte 1"),
+            ("{}}", "unexpected character '}' at byte 2"),
+            ("{{}", "unexpected end-of-file at byte 3 inside group, with 1 groups open"),
+            ("{<!>}", "unexpected end-of-file at byte 5 inside garbage, with 1 groups open"),
+            ("{}{}", "unexpected character '{' at byte 2"),
+            ("{}\n<>", "unexpected character '<' at byte 3"),
+            ("{{}{}}", "unexpected character '{' at byte 3"),
+            ("{<a><b>}", "unexpected character '<' at byte 4"),
+            ("{,{}}", "unexpected character ',' at byte 1"),
+            ("{{},}", "unexpected character '}' at byte 4"),
+            ("{{},,{}}", "unexpected character ',' at byte 4"),
+            ("", "empty stream, expecting a group or garbage"),
+            (" \n", "empty stream, expecting a group or garbage"),
+        ];
+
+        for t in tests {
+            let err = Totals::scan(t.0.as_bytes()).unwrap_err();
+            assert_eq!(err.to_string(), t.1);
+        }
+    }
 }
 
 const PUZZLE: &'static str = "Stream Processing";
@@ -48,18 +48,27 @@ fn main() {
         .split(|c| c == ',')
         .map(|x| x.parse().unwrap())
         .collect();
-    println!(":: Answer 1 is {}", hex::shortest_path(seq.iter()));
+    let walk = hex::walk(seq.iter());
+    if input.verbosity() > 0 {
+        println!("-> shortest path to {}: {}", walk.position, hex::join(&hex::HexCoord::origin().moves_to(&walk.position)));
+    }
+    println!(":: Answer 1 is {}", walk.position.length());
 
-    // Like I care about performance...
-    let max = (0..seq.len()).map(|n| hex::shortest_path(seq[0..n].iter())).max();
-    println!(":: Answer 2 is {}", max.unwrap());
+    let furthest = walk.furthest.expect("path is empty");
+    if input.verbosity() > 0 {
+        println!("-> furthest at {} after step {} of {}", furthest.position, furthest.index + 1, walk.steps);
+    }
+    println!(":: Answer 2 is {}", furthest.distance());
 }
 
-mod hex {
+pub mod hex {
 
+use std::error;
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Movement {
     N,
     Ne,
@@ -69,8 +78,38 @@ pub enum Movement {
     Sw,
 }
 
+impl Movement {
+    /// All movements, going counter-clockwise from southeast.
+    pub const ALL: [Movement; 6] = [Movement::Se, Movement::Ne, Movement::N, Movement::Nw, Movement::Sw, Movement::S];
+
+    /// Returns the coordinate of the neighbour of the origin in this direction.
+    pub fn offset(&self) -> HexCoord {
+        use hex::Movement::*;
+        match *self {
+            N => HexCoord::new(0, -1),
+            Ne => HexCoord::new(1, -1),
+            Se => HexCoord::new(1, 0),
+            S => HexCoord::new(0, 1),
+            Sw => HexCoord::new(-1, 1),
+            Nw => HexCoord::new(-1, 0),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        use hex::Movement::*;
+        match *self {
+            N => "n",
+            Ne => "ne",
+            Nw => "nw",
+            S => "s",
+            Se => "se",
+            Sw => "sw",
+        }
+    }
+}
+
 impl FromStr for Movement {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use hex::Movement::*;
@@ -81,53 +120,301 @@ impl FromStr for Movement {
             "s" => Ok(S),
             "se" => Ok(Se),
             "sw" => Ok(Sw),
-            _ => Err(()),
+            _ => Err(ParseError::with_msg(s, "unknown movement, expecting one of n, ne, nw, s, se, or sw")),
         }
     }
 }
 
-pub fn shortest_path<'a, I: Iterator<Item = &'a Movement>>(iter: I) -> usize {
-    use hex::Movement::*;
-
-    let mut ne: isize = 0;
-    let mut nw: isize = 0;
-    for m in iter {
-        match *m {
-            N => {
-                // Going N is the same as Ne + Nw
-                ne += 1;
-                nw += 1;
-            },
-            S => {
-                // Going S is the same as Se + Sw
-                ne -= 1;
-                nw -= 1;
-            },
-            Ne => ne += 1,
-            Sw => ne -= 1,
-            Nw => nw += 1,
-            Se => nw -= 1,
-        }
-    }
-
-    let (nb, nl) = order(ne, nw);
-    let res =
-        if nb.signum() == nl.signum() || nl == 0 {
-            nb.abs()
-        } else {
-            nb.abs() + nl.abs()
-        };
-    res as usize
-}
-
-fn order(a: isize, b: isize) -> (isize, isize) {
-    if a.abs() > b.abs() {
-        (a, b)
-    } else {
-        (b, a)
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Formats movements the way they appear in the input, such as `n,ne,ne`.
+pub fn join(moves: &[Movement]) -> String {
+    moves.iter().map(|m| m.as_str()).collect::<Vec<_>>().join(",")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    msg: &'static str,
+    data: String,
+}
+
+impl ParseError {
+    pub fn with_msg(data: &str, msg: &'static str) -> Self {
+        ParseError {
+            msg,
+            data: String::from(data),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.msg, self.data)
+    }
+}
+
+impl error::Error for ParseError {
+    fn description(&self) -> &str { self.msg }
+}
+
+/// A hex in the grid, in axial coordinates.
+///
+/// The axis `q` runs from northwest to southeast, and `r` from north to south.
+/// The third cube coordinate `s` is implied by `q + r + s == 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct HexCoord {
+    q: isize,
+    r: isize,
+}
+
+impl HexCoord {
+    pub fn new(q: isize, r: isize) -> Self {
+        HexCoord { q, r }
+    }
+
+    pub fn origin() -> Self {
+        Self::default()
+    }
+
+    /// Creates a coordinate from cube coordinates, which must sum to 0.
+    pub fn from_cube(q: isize, r: isize, s: isize) -> Self {
+        assert_eq!(q + r + s, 0, "cube coordinates must sum to 0");
+        HexCoord { q, r }
+    }
+
+    pub fn q(&self) -> isize { self.q }
+    pub fn r(&self) -> isize { self.r }
+    pub fn s(&self) -> isize { -self.q - self.r }
+
+    pub fn cube(&self) -> (isize, isize, isize) {
+        (self.q, self.r, self.s())
+    }
+
+    /// Returns the number of steps between two hexes.
+    pub fn distance(&self, other: &HexCoord) -> usize {
+        let d = *self - *other;
+        ((d.q.abs() + d.r.abs() + d.s().abs()) / 2) as usize
+    }
+
+    /// Returns the number of steps from the origin.
+    pub fn length(&self) -> usize {
+        self.distance(&HexCoord::origin())
+    }
+
+    /// Returns the six adjacent hexes, in the order of `Movement::ALL`.
+    pub fn neighbours(&self) -> Vec<HexCoord> {
+        Movement::ALL.iter().map(|m| *self + *m).collect()
+    }
+
+    /// Returns all hexes at exactly `radius` steps, going counter-clockwise
+    /// from the southwest corner. A ring of radius 0 is the hex itself.
+    pub fn ring(&self, radius: usize) -> Vec<HexCoord> {
+        if radius == 0 {
+            return vec![*self];
+        }
+        let mut res = Vec::with_capacity(6 * radius);
+        let mut hex = *self + Movement::Sw.offset() * radius as isize;
+        for m in Movement::ALL.iter() {
+            for _ in 0..radius {
+                res.push(hex);
+                hex = hex + *m;
+            }
+        }
+        res
+    }
+
+    /// Returns a shortest sequence of movements to `other`.
+    ///
+    /// The sequence is canonical: it uses at most two directions, which
+    /// are adjacent, and all movements in one direction come first, in
+    /// the order of `Movement::ALL`.
+    pub fn moves_to(&self, other: &HexCoord) -> Vec<Movement> {
+        let d = *other - *self;
+        for i in 0..6 {
+            let (m1, m2) = (Movement::ALL[i], Movement::ALL[(i + 1) % 6]);
+            let (a, b) = (m1.offset(), m2.offset());
+
+            // Solve d = x*a + y*b; adjacent directions form a basis of the grid.
+            let det = a.q * b.r - b.q * a.r;
+            let x = (d.q * b.r - b.q * d.r) / det;
+            let y = (a.q * d.r - d.q * a.r) / det;
+            if x >= 0 && y >= 0 {
+                let (first, second) = if i < 5 { ((m1, x), (m2, y)) } else { ((m2, y), (m1, x)) };
+                let mut moves = vec![first.0; first.1 as usize];
+                moves.extend(vec![second.0; second.1 as usize]);
+                return moves;
+            }
+        }
+        unreachable!("every hex lies in one of the six sectors")
+    }
+
+    /// Returns the hexes on a straight line to `other`, including both ends.
+    /// Consecutive hexes are neighbours.
+    pub fn line_to(&self, other: &HexCoord) -> Vec<HexCoord> {
+        let n = self.distance(other);
+        if n == 0 {
+            return vec![*self];
+        }
+        let lerp = |a: isize, b: isize, t: f64| a as f64 + (b - a) as f64 * t;
+        (0..n+1).map(|i| {
+            let t = i as f64 / n as f64;
+            // The nudge keeps points on an edge between two hexes from
+            // being rounded in different directions.
+            HexCoord::round(
+                lerp(self.q, other.q, t) + 1e-6,
+                lerp(self.r, other.r, t) + 1e-6,
+                lerp(self.s(), other.s(), t) - 2e-6,
+            )
+        }).collect()
+    }
+
+    /// Rounds fractional cube coordinates to the hex containing them.
+    fn round(q: f64, r: f64, s: f64) -> HexCoord {
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        HexCoord::new(rq as isize, rr as isize)
+    }
+}
+
+impl Add for HexCoord {
+    type Output = HexCoord;
+
+    fn add(self, other: HexCoord) -> HexCoord {
+        HexCoord::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl Add<Movement> for HexCoord {
+    type Output = HexCoord;
+
+    fn add(self, m: Movement) -> HexCoord {
+        self + m.offset()
+    }
+}
+
+impl Sub for HexCoord {
+    type Output = HexCoord;
+
+    fn sub(self, other: HexCoord) -> HexCoord {
+        HexCoord::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+impl Mul<isize> for HexCoord {
+    type Output = HexCoord;
+
+    fn mul(self, n: isize) -> HexCoord {
+        HexCoord::new(self.q * n, self.r * n)
+    }
+}
+
+impl fmt::Display for HexCoord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}, {})", self.q, self.r, self.s())
+    }
+}
+
+/// A single step along a `Path`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// Index of the movement, starting at 0.
+    pub index: usize,
+
+    /// Direction of the movement.
+    pub movement: Movement,
+
+    /// Hex reached by the movement.
+    pub position: HexCoord,
+}
+
+impl Step {
+    /// Returns the number of steps from the origin to the position.
+    pub fn distance(&self) -> usize {
+        self.position.length()
+    }
+}
+
+/// An iterator over every hex visited by a sequence of movements from the origin.
+pub struct Path<I> {
+    moves: I,
+    index: usize,
+    position: HexCoord,
+}
+
+impl<'a, I: Iterator<Item = &'a Movement>> Path<I> {
+    pub fn new(moves: I) -> Self {
+        Path {
+            moves,
+            index: 0,
+            position: HexCoord::origin(),
+        }
+    }
+
+    /// Returns the hex reached by the movements consumed so far.
+    pub fn position(&self) -> HexCoord {
+        self.position
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a Movement>> Iterator for Path<I> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        let movement = *self.moves.next()?;
+        self.position = self.position + movement;
+        let step = Step{ index: self.index, movement, position: self.position };
+        self.index += 1;
+        Some(step)
+    }
+}
+
+/// The result of walking a sequence of movements, as computed by `walk`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Walk {
+    /// Hex at the end of the walk.
+    pub position: HexCoord,
+
+    /// Number of movements.
+    pub steps: usize,
+
+    /// The first step at the largest distance from the origin,
+    /// or `None` if there were no movements.
+    pub furthest: Option<Step>,
+}
+
+/// Walks the movements in a single pass, keeping track of the furthest step.
+pub fn walk<'a, I: Iterator<Item = &'a Movement>>(iter: I) -> Walk {
+    let mut path = Path::new(iter);
+    let mut steps = 0;
+    let mut furthest: Option<Step> = None;
+    for step in path.by_ref() {
+        steps += 1;
+        if furthest.is_none_or(|f| step.distance() > f.distance()) {
+            furthest = Some(step);
+        }
+    }
+    Walk {
+        position: path.position(),
+        steps,
+        furthest,
     }
 }
 
+pub fn shortest_path<'a, I: Iterator<Item = &'a Movement>>(iter: I) -> usize {
+    let mut path = Path::new(iter);
+    path.by_ref().last();
+    path.position().length()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,6 +437,123 @@ mod tests {
             assert_eq!(shortest_path(t.0.iter()), t.1, "input = {:?}", t.0);
         }
     }
+
+    #[test]
+    fn test_walk() {
+        use hex::Movement::*;
+        let tests = vec![
+            (vec![], 0, None),
+            (vec![Ne,Ne,Sw,Sw], 0, Some((1, 2))),
+            (vec![Se,Sw,Se,Sw,Sw], 3, Some((4, 3))),
+            (vec![N,N,S,S,N,N,N], 3, Some((6, 3))),
+            (vec![N,S,S,N,N], 1, Some((0, 1))),
+        ];
+
+        for t in tests {
+            let walk = walk(t.0.iter());
+            assert_eq!(walk.steps, t.0.len());
+            assert_eq!(walk.position.length(), t.1, "input = {:?}", t.0);
+            assert_eq!(walk.furthest.map(|x| (x.index, x.distance())), t.2, "input = {:?}", t.0);
+        }
+    }
+
+    #[test]
+    fn test_moves_to() {
+        use hex::Movement::*;
+        let tests = vec![
+            (vec![], ""),
+            (vec![Ne,Ne,S,S], "se,se"),
+            (vec![Se,Sw,Se,Sw,Sw], "sw,s,s"),
+            (vec![Nw,S,Sw,N,Sw], "nw,sw,sw"),
+            (vec![Se,S,Se], "se,se,s"),
+            (vec![N,N,Nw,Ne,Ne], "ne,n,n,n"),
+        ];
+
+        for t in tests {
+            let end = walk(t.0.iter()).position;
+            let moves = HexCoord::origin().moves_to(&end);
+            assert_eq!(join(&moves), t.1, "input = {:?}", t.0);
+            assert_eq!(moves.len(), end.length());
+            assert_eq!(walk(moves.iter()).position, end);
+        }
+
+        for target in HexCoord::new(2, -1).ring(3) {
+            let moves = HexCoord::new(2, -1).moves_to(&target);
+            assert_eq!(moves.len(), 3);
+            assert!(moves.windows(2).filter(|w| w[0] != w[1]).count() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let moves: Result<Vec<Movement>, ParseError> = "n,se,sw".split(',').map(|x| x.parse()).collect();
+        assert_eq!(join(&moves.unwrap()), "n,se,sw");
+        let err = "n,east".split(',').map(|x| x.parse::<Movement>()).collect::<Result<Vec<_>, _>>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown movement, expecting one of n, ne, nw, s, se, or sw: \"east\"");
+    }
+
+    #[test]
+    fn test_coord() {
+        let a = HexCoord::new(2, -3);
+        assert_eq!(a.cube(), (2, -3, 1));
+        assert_eq!(HexCoord::from_cube(2, -3, 1), a);
+        assert_eq!(a + HexCoord::new(-2, 3), HexCoord::origin());
+        assert_eq!(a + Movement::S, HexCoord::new(2, -2));
+        assert_eq!(a.length(), 3);
+        assert_eq!(a.distance(&HexCoord::new(-1, 1)), 4);
+
+        let neighbours = a.neighbours();
+        assert_eq!(neighbours.len(), 6);
+        for (i, n) in neighbours.iter().enumerate() {
+            assert_eq!(a.distance(n), 1);
+            assert!(!neighbours[..i].contains(n));
+        }
+    }
+
+    #[test]
+    fn test_ring() {
+        let c = HexCoord::new(1, 1);
+        assert_eq!(c.ring(0), vec![c]);
+        for radius in 1..5 {
+            let ring = c.ring(radius);
+            assert_eq!(ring.len(), 6 * radius);
+            for (i, x) in ring.iter().enumerate() {
+                assert_eq!(c.distance(x), radius);
+                assert_eq!(x.distance(&ring[(i + 1) % ring.len()]), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_to() {
+        let tests = vec![
+            (HexCoord::origin(), HexCoord::origin()),
+            (HexCoord::origin(), HexCoord::new(3, 0)),
+            (HexCoord::new(-2, 1), HexCoord::new(3, -4)),
+            (HexCoord::new(0, 0), HexCoord::new(2, -4)),
+            (HexCoord::new(4, -1), HexCoord::new(-3, 5)),
+        ];
+
+        for t in tests {
+            let line = t.0.line_to(&t.1);
+            assert_eq!(line.len(), t.0.distance(&t.1) + 1);
+            assert_eq!((line[0], line[line.len() - 1]), t);
+            for w in line.windows(2) {
+                assert_eq!(w[0].distance(&w[1]), 1, "line = {:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_path() {
+        use hex::Movement::*;
+        let moves = [Se, Sw, Se, Sw, Sw];
+        let steps: Vec<Step> = Path::new(moves.iter()).collect();
+        let distances: Vec<usize> = steps.iter().map(|x| x.distance()).collect();
+        assert_eq!(distances, vec![1, 1, 2, 2, 3]);
+        assert_eq!(steps[2], Step{ index: 2, movement: Se, position: HexCoord::new(1, 1) });
+        assert_eq!(steps[4].position, HexCoord::new(-1, 3));
+    }
 }
 
 }
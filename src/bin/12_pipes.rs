@@ -69,17 +69,75 @@ How many groups are there in total?
 */
 
 extern crate aoc;
+extern crate clap;
+
+use clap::Arg;
+use std::fs::File;
+use std::io::BufWriter;
 
 fn main() {
-    let mut input = aoc::ProgramInput::new(PUZZLE, INPUT);
+    let mut input = aoc::ProgramInput::with_args(PUZZLE, INPUT, vec![
+        Arg::with_name("check")
+            .long("check")
+            .help("Report asymmetric, looping, duplicate and undeclared pipes"),
+        Arg::with_name("symmetrize")
+            .long("symmetrize")
+            .help("Add the missing half of every one-way pipe before solving"),
+        Arg::with_name("groups")
+            .long("groups")
+            .help("List every group with its size, diameter, articulation points and members"),
+        Arg::with_name("dot")
+            .long("dot")
+            .value_name("FILE")
+            .help("Write the pipes as a Graphviz graph coloured by group to FILE"),
+    ]);
     println!("Day 12: {}", PUZZLE);
 
-    let conns: Vec<_> = input.to_str().lines().map(|x| x.parse().unwrap()).collect();
-    let set = pipes::root_group(conns.iter());
-    println!(":: Answer 1 is {}", set.len());
+    let verbose = input.verbosity() > 0;
+    let check = input.is_present("check");
+    let symmetrize = input.is_present("symmetrize");
+    let list = input.is_present("groups");
+    let dot = input.value_of("dot").map(String::from);
+    let mut conns = match pipes::parse(input.to_str()) {
+        Ok(v) => v,
+        Err(e) => {
+            println!(":: Error: {}", e);
+            return;
+        }
+    };
+    if check {
+        for p in pipes::validate(&conns) {
+            println!("warning: {}", p);
+        }
+    }
+    if symmetrize {
+        let added = pipes::symmetrize(&mut conns);
+        println!("-> added {} missing pipes", added);
+        if verbose {
+            conns.iter().for_each(|c| println!("{}", c));
+        }
+    }
 
-    let all = pipes::all_groups(conns.iter());
-    println!(":: Answer 2 is {}", all.len());
+    let net = pipes::Network::new(&conns);
+    let groups = net.graph().components();
+    if list {
+        for g in pipes::group_stats(&net, &groups) {
+            println!("{}", g);
+        }
+    }
+    if let Some(path) = dot {
+        let mut w = BufWriter::new(File::create(path).expect("cannot create dot file"));
+        pipes::write_dot(&mut w, &net, &groups).expect("cannot write dot file");
+    }
+    let root = match net.node(0) {
+        Some(n) => n,
+        None => {
+            println!(":: Error: program 0 does not appear in the input");
+            return;
+        }
+    };
+    println!(":: Answer 1 is {}", groups.size(groups.label(root)));
+    println!(":: Answer 2 is {}", groups.len());
 }
 
 mod pipes {
@@ -87,46 +145,224 @@ mod pipes {
 use std::str::FromStr;
 use std::fmt;
 use std::error;
-use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use aoc::graph::{Components, Graph};
 
-pub fn group_for(map: &HashMap<usize, Vec<usize>>, root: usize) -> HashSet<usize> {
-    let mut set = HashSet::new();
-    let mut stack: Vec<usize> = vec![root];
-    while let Some(ref x) = stack.pop() {
-        // Prevent loops by ignoring x if it was already looked at:
-        if set.contains(x) {
-            continue;
+/// The graph of programs, with an edge for every pipe.
+///
+/// Program IDs need not be contiguous, so every ID that is declared or
+/// referenced is mapped to a node index. Indices follow the order of the
+/// IDs, so components are still labeled in the order of their smallest ID.
+#[derive(Debug, Clone)]
+pub struct Network {
+    graph: Graph,
+    ids: Vec<usize>,
+}
+
+impl Network {
+    pub fn new(conns: &[Connection]) -> Self {
+        let ids: BTreeSet<usize> = conns.iter()
+            .flat_map(|c| Some(c.from).into_iter().chain(c.to.iter().cloned()))
+            .collect();
+        let mut net = Self {
+            graph: Graph::with_nodes(ids.len()),
+            ids: ids.into_iter().collect(),
+        };
+        for c in conns {
+            let a = net.node(c.from).unwrap();
+            for &y in &c.to {
+                let b = net.node(y).unwrap();
+                net.graph.add_edge(a, b);
+            }
         }
+        net
+    }
+
+    pub fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    /// Returns the program ID of a node.
+    pub fn id(&self, node: usize) -> usize {
+        self.ids[node]
+    }
+
+    /// Returns the node of a program ID, if it appears in the list.
+    pub fn node(&self, id: usize) -> Option<usize> {
+        self.ids.binary_search(&id).ok()
+    }
+}
+
+/// A group of programs together with some statistics about its shape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupStats {
+    pub label: usize,
+    pub members: Vec<usize>,
+    /// The longest shortest path between two members.
+    pub diameter: usize,
+    /// The members whose removal would split the group.
+    pub articulation: Vec<usize>,
+}
+
+impl fmt::Display for GroupStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |v: &[usize]| v.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ");
+        writeln!(f, "-> group {} ({} programs, diameter {}): {}",
+                 self.label, self.members.len(), self.diameter, join(&self.members))?;
+        write!(f, "   articulation points: {}",
+               if self.articulation.is_empty() { String::from("none") } else { join(&self.articulation) })
+    }
+}
+
+/// Returns the statistics of every group, largest first.
+///
+/// Groups of the same size are ordered by label, so that the group
+/// containing program 0 comes first among its equals.
+pub fn group_stats(net: &Network, groups: &Components) -> Vec<GroupStats> {
+    let g = net.graph();
+    let mut articulation = vec![Vec::new(); groups.len()];
+    for x in g.articulation_points() {
+        articulation[groups.label(x)].push(net.id(x));
+    }
+
+    let mut stats: Vec<GroupStats> = groups.groups().into_iter()
+        .zip(articulation)
+        .enumerate()
+        .map(|(label, (members, articulation))| GroupStats {
+            label,
+            diameter: members.iter().map(|&x| g.eccentricity(x)).max().unwrap_or(0),
+            members: members.into_iter().map(|x| net.id(x)).collect(),
+            articulation,
+        })
+        .collect();
+    stats.sort_by(|a, b| b.members.len().cmp(&a.members.len()).then(a.label.cmp(&b.label)));
+    stats
+}
+
+/// Writes the graph in Graphviz DOT format, giving every group its own colour.
+pub fn write_dot<W: Write>(w: &mut W, net: &Network, groups: &Components) -> io::Result<()> {
+    // Stepping through the hues by the golden ratio keeps the colours of
+    // neighbouring labels far apart, however many groups there are.
+    const GOLDEN: f64 = 0.618_033_988_749_895;
+    writeln!(w, "graph pipes {{")?;
+    writeln!(w, "    node [style=filled];")?;
+    for (x, &label) in groups.labels().iter().enumerate() {
+        let hue = (label as f64 * GOLDEN).fract();
+        writeln!(w, "    {} [fillcolor=\"{:.3} 0.500 0.950\"];", net.id(x), hue)?;
+    }
+    for (a, b) in net.graph().edges() {
+        writeln!(w, "    {} -- {};", net.id(a), net.id(b))?;
+    }
+    writeln!(w, "}}")
+}
+
+/// Parses one connection per line, attaching the line number to errors.
+pub fn parse(s: &str) -> Result<Vec<Connection>, ParseError> {
+    s.lines()
+        .enumerate()
+        .map(|(i, line)| line.parse().map_err(|e: ParseError| e.on_line(i + 1)))
+        .collect()
+}
+
+/// A problem in a list of connections that does not prevent solving it.
+///
+/// Line numbers are 1-based positions in the list of connections, which
+/// match the input lines when the list was created by `parse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    /// Warning due to a pipe that is listed by only one of its ends.
+    Asymmetric { line: usize, from: usize, to: usize },
+    /// Warning due to a program that lists itself.
+    SelfLoop { line: usize, node: usize },
+    /// Warning due to a program that is declared more than once.
+    Duplicate { line: usize, node: usize, first: usize },
+    /// Warning due to a pipe to a program that is never declared.
+    Undeclared { line: usize, from: usize, to: usize },
+}
 
-        set.insert(*x);
-        if let Some(vec) =  map.get(x) {
-            vec.iter().for_each(|v| stack.push(*v));
+impl Problem {
+    pub fn line(&self) -> usize {
+        match *self {
+            Problem::Asymmetric { line, .. } |
+            Problem::SelfLoop { line, .. } |
+            Problem::Duplicate { line, .. } |
+            Problem::Undeclared { line, .. } => line,
         }
     }
-    set
 }
 
-pub fn all_groups<'a, I: Iterator<Item = &'a Connection>>(iter: I) -> Vec<HashSet<usize>> {
-    let mut map = HashMap::new();
-    for x in iter {
-        map.insert(x.from, x.to.clone());
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::Asymmetric { line, from, to } =>
+                write!(f, "line {}: {} lists {}, but {} does not list {}", line, from, to, to, from),
+            Problem::SelfLoop { line, node } =>
+                write!(f, "line {}: {} is connected to itself", line, node),
+            Problem::Duplicate { line, node, first } =>
+                write!(f, "line {}: {} was already declared on line {}", line, node, first),
+            Problem::Undeclared { line, from, to } =>
+                write!(f, "line {}: {} lists {}, which is never declared", line, from, to),
+        }
+    }
+}
+
+/// Checks that every pipe is listed by both of its ends, and reports
+/// everything else that the puzzle description promises not to happen.
+///
+/// The lists of a program that is declared more than once are merged
+/// before checking for asymmetric pipes. Problems are ordered by line.
+pub fn validate(conns: &[Connection]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut declared = HashMap::new();
+    let mut links: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (i, c) in conns.iter().enumerate() {
+        match declared.get(&c.from) {
+            Some(&first) => problems.push(Problem::Duplicate { line: i + 1, node: c.from, first }),
+            None => { declared.insert(c.from, i + 1); }
+        }
+        links.entry(c.from).or_default().extend(&c.to);
     }
 
-    let mut all = Vec::new();
-    while let Some(&x) = map.keys().next() {
-        let set = group_for(&map, x);
-        set.iter().for_each(|y| { map.remove(y); });
-        all.push(set);
+    for (i, c) in conns.iter().enumerate() {
+        let line = i + 1;
+        for &to in &c.to {
+            if to == c.from {
+                problems.push(Problem::SelfLoop { line, node: to });
+                continue;
+            }
+            match links.get(&to) {
+                None => problems.push(Problem::Undeclared { line, from: c.from, to }),
+                Some(set) if !set.contains(&c.from) => {
+                    problems.push(Problem::Asymmetric { line, from: c.from, to })
+                }
+                _ => {}
+            }
+        }
     }
-    all
+    problems.sort_by_key(Problem::line);
+    problems
 }
 
-pub fn root_group<'a, I: Iterator<Item = &'a Connection>>(iter: I) -> HashSet<usize> {
-    let mut map = HashMap::new();
-    for x in iter {
-        map.insert(x.from, x.to.clone());
+/// Adds the missing half of every asymmetric pipe, declaring programs
+/// that were only referenced at the end of the list.
+///
+/// Returns the number of pipes that were added.
+pub fn symmetrize(conns: &mut Vec<Connection>) -> usize {
+    let mut added = 0;
+    for p in validate(conns) {
+        let (from, to) = match p {
+            Problem::Asymmetric { from, to, .. } | Problem::Undeclared { from, to, .. } => (from, to),
+            _ => continue,
+        };
+        match conns.iter_mut().find(|c| c.from == to) {
+            Some(c) if c.to.contains(&from) => continue,
+            Some(c) => c.to.push(from),
+            None => conns.push(Connection { from: to, to: vec![from] }),
+        }
+        added += 1;
     }
-    group_for(&map, 0)
+    added
 }
 
 #[derive(Debug)]
@@ -145,8 +381,9 @@ impl FromStr for Connection {
         }
         let from = list[0].parse()
             .or(Err(ParseError::new(s, "cannot parse number from index 0")))?;
-        let rest: Vec<usize> = list[1].split(", ")
-            .map(|s| s.parse().unwrap()).collect();
+        let rest = list[1].split(", ")
+            .map(|x| x.parse().or(Err(ParseError::new(s, "cannot parse number from index 1"))))
+            .collect::<Result<Vec<usize>, _>>()?;
         Ok(Self{
             from: from,
             to: rest,
@@ -154,10 +391,18 @@ impl FromStr for Connection {
     }
 }
 
+impl fmt::Display for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let to: Vec<String> = self.to.iter().map(|x| x.to_string()).collect();
+        write!(f, "{} <-> {}", self.from, to.join(", "))
+    }
+}
+
 #[derive(Debug)]
 pub struct ParseError {
     msg: &'static str,
     line: String,
+    number: Option<usize>,
 }
 
 impl ParseError {
@@ -165,8 +410,14 @@ impl ParseError {
         Self {
             msg: msg,
             line: String::from(line),
+            number: None,
         }
     }
+
+    /// Records the line number of the input that could not be parsed.
+    pub fn on_line(self, number: usize) -> Self {
+        Self { number: Some(number), ..self }
+    }
 }
 
 impl error::Error for ParseError {
@@ -177,10 +428,113 @@ impl error::Error for ParseError {
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "parse error: '{}' for input: {}", self.msg, self.line)
+        match self.number {
+            Some(n) => write!(f, "parse error on line {}: '{}' for input: {}", n, self.msg, self.line),
+            None => write!(f, "parse error: '{}' for input: {}", self.msg, self.line),
+        }
+    }
+}
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "0 <-> 2\n1 <-> 1\n2 <-> 0, 3, 4\n3 <-> 2, 4\n4 <-> 2, 3, 6\n5 <-> 6\n6 <-> 4, 5";
+
+    #[test]
+    fn test_example() {
+        let conns = pipes::parse(EXAMPLE).unwrap();
+        let groups = pipes::Network::new(&conns).graph().components();
+        assert_eq!(groups.size(groups.label(0)), 6);
+        assert_eq!(groups.len(), 2);
+    }
+
+    #[test]
+    fn test_sparse_ids() {
+        let tests = vec![
+            ("0 <-> 2\n2 <-> 0", 2, 1),
+            ("0 <-> 0\n7 <-> 9\n9 <-> 7", 1, 2),
+            ("5 <-> 1000000000000\n1000000000000 <-> 5, 0\n0 <-> 1000000000000", 3, 1),
+        ];
+
+        for t in tests {
+            let net = pipes::Network::new(&pipes::parse(t.0).unwrap());
+            let groups = net.graph().components();
+            let root = net.node(0).unwrap();
+            assert_eq!(groups.size(groups.label(root)), t.1, "input = {:?}", t.0);
+            assert_eq!(groups.len(), t.2, "input = {:?}", t.0);
+        }
+
+        let net = pipes::Network::new(&pipes::parse("1 <-> 2\n2 <-> 1").unwrap());
+        assert_eq!(net.node(0), None);
+        assert_eq!(net.id(1), 2);
+    }
+
+    #[test]
+    fn test_group_stats() {
+        let conns = pipes::parse(EXAMPLE).unwrap();
+        let net = pipes::Network::new(&conns);
+        let stats = pipes::group_stats(&net, &net.graph().components());
+        assert_eq!(stats, vec![
+            pipes::GroupStats { label: 0, members: vec![0, 2, 3, 4, 5, 6], diameter: 4, articulation: vec![2, 4, 6] },
+            pipes::GroupStats { label: 1, members: vec![1], diameter: 0, articulation: vec![] },
+        ]);
+        assert_eq!(stats[1].to_string(), "-> group 1 (1 programs, diameter 0): 1\n   articulation points: none");
     }
+
+    #[test]
+    fn test_write_dot() {
+        let conns = pipes::parse("0 <-> 10\n10 <-> 0\n20 <-> 20").unwrap();
+        let net = pipes::Network::new(&conns);
+        let mut buf = Vec::new();
+        pipes::write_dot(&mut buf, &net, &net.graph().components()).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "graph pipes {
+    node [style=filled];
+    0 [fillcolor=\"0.000 0.500 0.950\"];
+    10 [fillcolor=\"0.000 0.500 0.950\"];
+    20 [fillcolor=\"0.618 0.500 0.950\"];
+    0 -- 10;
+    20 -- 20;
 }
+");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let tests = vec![
+            ("0 <-> 1\n1 <-> 0, x", "parse error on line 2: 'cannot parse number from index 1' for input: 1 <-> 0, x"),
+            ("0 <-> 1\n1 <-> 0\n2 -> 1", "parse error on line 3: 'expecting format NUM <-> NUM, NUM, ...' for input: 2 -> 1"),
+        ];
 
+        for t in tests {
+            let err = pipes::parse(t.0).unwrap_err();
+            assert_eq!(err.to_string(), t.1, "input = {:?}", t.0);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        use pipes::Problem::*;
+        let conns = pipes::parse("0 <-> 2, 3\n1 <-> 1\n2 <-> 0, 5\n3 <-> 1\n1 <-> 3").unwrap();
+        assert_eq!(pipes::validate(&conns), vec![
+            Asymmetric { line: 1, from: 0, to: 3 },
+            SelfLoop { line: 2, node: 1 },
+            Undeclared { line: 3, from: 2, to: 5 },
+            Duplicate { line: 5, node: 1, first: 2 },
+        ]);
+        assert!(pipes::validate(&pipes::parse(EXAMPLE).unwrap()).iter().all(|p| p.line() == 2));
+    }
+
+    #[test]
+    fn test_symmetrize() {
+        let mut conns = pipes::parse("0 <-> 2, 3\n2 <-> 5\n3 <-> 0").unwrap();
+        assert_eq!(pipes::symmetrize(&mut conns), 2);
+        let lines: Vec<String> = conns.iter().map(|c| c.to_string()).collect();
+        assert_eq!(lines, vec!["0 <-> 2, 3", "2 <-> 5, 0", "3 <-> 0", "5 <-> 2"]);
+        assert!(pipes::validate(&conns).is_empty());
+    }
 }
 
 const PUZZLE: &'static str = "Digital Plumber";
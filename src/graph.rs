@@ -0,0 +1,380 @@
+//! Undirected graphs over dense node indices.
+//!
+//! Nodes are identified by `usize` indices from `0` to `len() - 1`, which
+//! makes it possible to keep all per-node data in plain vectors. Adding an
+//! edge to a node that does not exist yet grows the graph as needed, so
+//! sparse identifiers should be mapped to compact indices first; otherwise
+//! every unused index in between becomes an isolated node.
+
+use std::collections::VecDeque;
+
+/// An undirected graph stored as adjacency lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    adjacent: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a graph with `n` nodes and no edges.
+    pub fn with_nodes(n: usize) -> Self {
+        Self { adjacent: vec![Vec::new(); n] }
+    }
+
+    /// Returns the number of nodes.
+    pub fn len(&self) -> usize {
+        self.adjacent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.adjacent.is_empty()
+    }
+
+    /// Makes sure that node `n` exists, growing the graph if necessary.
+    pub fn add_node(&mut self, n: usize) {
+        if n >= self.adjacent.len() {
+            self.adjacent.resize(n + 1, Vec::new());
+        }
+    }
+
+    /// Adds an undirected edge between `a` and `b`, unless it already exists.
+    ///
+    /// A self-loop is only stored once in the adjacency list of its node.
+    /// Returns false if the edge was already present.
+    pub fn add_edge(&mut self, a: usize, b: usize) -> bool {
+        self.add_node(a.max(b));
+        if self.adjacent[a].contains(&b) {
+            return false;
+        }
+        self.adjacent[a].push(b);
+        if a != b {
+            self.adjacent[b].push(a);
+        }
+        true
+    }
+
+    /// Returns the nodes adjacent to `n`, in the order the edges were added.
+    pub fn neighbours(&self, n: usize) -> &[usize] {
+        &self.adjacent[n]
+    }
+
+    /// Returns every edge once, as `(a, b)` with `a <= b`.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.adjacent.iter().enumerate().flat_map(|(a, list)| {
+            list.iter().filter(move |&&b| a <= b).map(move |&b| (a, b))
+        })
+    }
+
+    /// Returns the length of the shortest path from `start` to every node,
+    /// or `None` for nodes that cannot be reached, using a breadth-first search.
+    pub fn distances(&self, start: usize) -> Vec<Option<usize>> {
+        let mut dist = vec![None; self.len()];
+        let mut queue = VecDeque::new();
+        dist[start] = Some(0);
+        queue.push_back(start);
+        while let Some(n) = queue.pop_front() {
+            let d = dist[n].unwrap() + 1;
+            for &m in &self.adjacent[n] {
+                if dist[m].is_none() {
+                    dist[m] = Some(d);
+                    queue.push_back(m);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Returns the greatest distance from `n` to any node reachable from it.
+    pub fn eccentricity(&self, n: usize) -> usize {
+        self.distances(n).into_iter().flatten().max().unwrap_or(0)
+    }
+
+    /// Returns the nodes whose removal would split their component, in
+    /// increasing order.
+    ///
+    /// This is Tarjan's algorithm, with an explicit stack instead of
+    /// recursion so that long paths do not overflow the call stack.
+    pub fn articulation_points(&self) -> Vec<usize> {
+        let n = self.len();
+        let mut order: Vec<Option<usize>> = vec![None; n];
+        let mut low = vec![0; n];
+        let mut is_cut = vec![false; n];
+        let mut counter = 0;
+        for root in 0..n {
+            if order[root].is_some() {
+                continue;
+            }
+            order[root] = Some(counter);
+            low[root] = counter;
+            counter += 1;
+
+            // Every entry is (node, parent, index of the next neighbour to visit).
+            let mut root_children = 0;
+            let mut stack = vec![(root, root, 0)];
+            while let Some(top) = stack.last_mut() {
+                let (v, parent) = (top.0, top.1);
+                if let Some(&w) = self.adjacent[v].get(top.2) {
+                    top.2 += 1;
+                    match order[w] {
+                        Some(o) if w != parent => low[v] = low[v].min(o),
+                        Some(_) => {}
+                        None => {
+                            order[w] = Some(counter);
+                            low[w] = counter;
+                            counter += 1;
+                            stack.push((w, v, 0));
+                        }
+                    }
+                    continue;
+                }
+
+                stack.pop();
+                if v == root {
+                    continue;
+                }
+                low[parent] = low[parent].min(low[v]);
+                if parent == root {
+                    root_children += 1;
+                } else if Some(low[v]) >= order[parent] {
+                    is_cut[parent] = true;
+                }
+            }
+            is_cut[root] = root_children > 1;
+        }
+        (0..n).filter(|&x| is_cut[x]).collect()
+    }
+
+    /// Labels the connected components of the graph.
+    pub fn components(&self) -> Components {
+        let mut sets = UnionFind::new(self.len());
+        for (a, b) in self.edges() {
+            sets.union(a, b);
+        }
+        Components::from_sets(&mut sets)
+    }
+}
+
+/// A disjoint-set forest with union by size and path compression.
+#[derive(Debug, Clone)]
+pub struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    count: usize,
+}
+
+impl UnionFind {
+    /// Creates `n` singleton sets.
+    pub fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+            count: n,
+        }
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Returns the number of disjoint sets.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the representative of the set containing `x`.
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut x = x;
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    /// Merges the sets containing `a` and `b`.
+    ///
+    /// Returns false if they were already in the same set.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        let (big, small) = if self.size[a] >= self.size[b] { (a, b) } else { (b, a) };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+        self.count -= 1;
+        true
+    }
+
+    /// Returns the size of the set containing `x`.
+    pub fn size_of(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+}
+
+/// The connected components of a graph.
+///
+/// Components are numbered in the order of their smallest node, so the
+/// component containing node 0 always has label 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Components {
+    labels: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl Components {
+    /// Labels the sets of a union-find structure.
+    pub fn from_sets(sets: &mut UnionFind) -> Self {
+        let mut roots = vec![None; sets.len()];
+        let mut labels = Vec::with_capacity(sets.len());
+        let mut sizes = Vec::with_capacity(sets.count());
+        for x in 0..sets.len() {
+            let root = sets.find(x);
+            let label = *roots[root].get_or_insert_with(|| {
+                sizes.push(0);
+                sizes.len() - 1
+            });
+            sizes[label] += 1;
+            labels.push(label);
+        }
+        Self { labels, sizes }
+    }
+
+    /// Returns the number of components.
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// Returns the label of the component containing node `n`.
+    pub fn label(&self, n: usize) -> usize {
+        self.labels[n]
+    }
+
+    /// Returns the labels of all nodes, indexed by node.
+    pub fn labels(&self) -> &[usize] {
+        &self.labels
+    }
+
+    /// Returns the number of nodes in component `label`.
+    pub fn size(&self, label: usize) -> usize {
+        self.sizes[label]
+    }
+
+    /// Returns the nodes in component `label`, in increasing order.
+    pub fn members(&self, label: usize) -> Vec<usize> {
+        (0..self.labels.len()).filter(|&n| self.labels[n] == label).collect()
+    }
+
+    /// Returns the nodes of every component, indexed by label.
+    pub fn groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = self.sizes.iter().map(|&s| Vec::with_capacity(s)).collect();
+        for (n, &label) in self.labels.iter().enumerate() {
+            groups[label].push(n);
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Graph {
+        // 0 - 2 - 3      1 (self-loop)
+        //      \  |
+        //       - 4 - 6 - 5
+        let mut g = Graph::new();
+        for &(a, b) in &[(0, 2), (1, 1), (2, 3), (2, 4), (3, 4), (4, 6), (5, 6)] {
+            g.add_edge(a, b);
+        }
+        g
+    }
+
+    #[test]
+    fn test_edges() {
+        let g = example();
+        assert_eq!(g.len(), 7);
+        assert_eq!(g.neighbours(2), &[0, 3, 4]);
+        assert_eq!(g.neighbours(1), &[1]);
+        assert_eq!(g.edges().count(), 7);
+
+        let mut g = g;
+        assert!(!g.add_edge(3, 2));
+        assert!(g.add_edge(0, 9));
+        assert_eq!(g.len(), 10);
+        assert_eq!(g.edges().count(), 8);
+    }
+
+    #[test]
+    fn test_distances() {
+        let g = example();
+        let d = g.distances(0);
+        assert_eq!(d, vec![Some(0), None, Some(1), Some(2), Some(2), Some(4), Some(3)]);
+    }
+
+    #[test]
+    fn test_eccentricity() {
+        let g = example();
+        let tests = vec![(0, 4), (1, 0), (2, 3), (4, 2), (5, 4)];
+
+        for t in tests {
+            assert_eq!(g.eccentricity(t.0), t.1, "node = {}", t.0);
+        }
+    }
+
+    #[test]
+    fn test_articulation_points() {
+        assert_eq!(example().articulation_points(), vec![2, 4, 6]);
+
+        // A cycle has none, until a tail is attached to it.
+        let mut g = Graph::new();
+        for &(a, b) in &[(0, 1), (1, 2), (2, 3), (3, 0)] {
+            g.add_edge(a, b);
+        }
+        assert_eq!(g.articulation_points(), vec![]);
+        g.add_edge(0, 4);
+        assert_eq!(g.articulation_points(), vec![0]);
+    }
+
+    #[test]
+    fn test_union_find() {
+        let mut sets = UnionFind::new(5);
+        assert!(sets.union(0, 1));
+        assert!(sets.union(3, 4));
+        assert!(!sets.union(1, 0));
+        assert!(sets.union(1, 4));
+        assert_eq!(sets.count(), 2);
+        assert_eq!(sets.size_of(3), 4);
+        assert_eq!(sets.size_of(2), 1);
+        assert_eq!(sets.find(0), sets.find(4));
+    }
+
+    #[test]
+    fn test_components() {
+        let c = example().components();
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.label(0), 0);
+        assert_eq!(c.label(1), 1);
+        assert_eq!(c.size(0), 6);
+        assert_eq!(c.members(0), vec![0, 2, 3, 4, 5, 6]);
+        assert_eq!(c.groups(), vec![vec![0, 2, 3, 4, 5, 6], vec![1]]);
+    }
+}
@@ -1,18 +1,29 @@
+pub mod graph;
 pub mod knot;
+pub mod tree;
 
 extern crate clap;
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 
+use std::collections::HashMap;
 use std::fs::File;
+use std::hash::Hash;
 use std::io::{self, Read};
 
 pub struct ProgramInput {
     data: Option<String>,
     print: bool,
+    matches: ArgMatches<'static>,
 }
 
 impl ProgramInput {
     pub fn new(name: &str, default: &str) -> Self {
+        ProgramInput::with_args(name, default, Vec::new())
+    }
+
+    /// Creates a `ProgramInput` that accepts additional, puzzle-specific
+    /// arguments. These can be queried with `value_of` and `is_present`.
+    pub fn with_args(name: &str, default: &str, args: Vec<Arg<'static, 'static>>) -> Self {
         let matches = App::new(name)
             .author("Ben Morgan <neembi@gmail.com")
             .arg(
@@ -25,36 +36,48 @@ impl ProgramInput {
                     .short("v")
                     .long("verbose")
                     .multiple(true)
-                    .help("Print the default input used"),
+                    .help("Print more information, such as the default input used"),
             )
+            .args(&args)
             .get_matches();
 
-        if let Some(input) = matches.value_of("INPUT") {
-            if input == "-" {
-                // We will read stdin later.
-                ProgramInput {
-                    data: None,
-                    print: matches.occurrences_of("verbose") > 2,
-                }
-            } else {
+        let verbose = matches.occurrences_of("verbose");
+        let (data, print) = match matches.value_of("INPUT") {
+            // We will read stdin later.
+            Some("-") => (None, verbose > 2),
+            Some(input) => {
                 // Try to read input as a file.
                 let mut f = File::open(input).expect("file not found");
                 let mut contents = String::new();
                 f.read_to_string(&mut contents)
                     .expect("error reading the file");
-                ProgramInput {
-                    data: Some(contents),
-                    print: matches.occurrences_of("verbose") > 1,
-                }
-            }
-        } else {
-            ProgramInput {
-                data: Some(String::from(default.trim())),
-                print: matches.occurrences_of("verbose") > 0,
+                (Some(contents), verbose > 1)
             }
+            None => (Some(String::from(default.trim())), verbose > 0),
+        };
+
+        ProgramInput {
+            data,
+            print,
+            matches,
         }
     }
 
+    /// Returns how often the verbose flag was given.
+    pub fn verbosity(&self) -> u64 {
+        self.matches.occurrences_of("verbose")
+    }
+
+    /// Returns the value of a puzzle-specific argument.
+    pub fn value_of(&self, name: &str) -> Option<&str> {
+        self.matches.value_of(name)
+    }
+
+    /// Returns true if a puzzle-specific argument was given.
+    pub fn is_present(&self, name: &str) -> bool {
+        self.matches.is_present(name)
+    }
+
     pub fn to_str(&mut self) -> &str {
         if self.data.is_none() {
             println!(":: Reading from stdin...");
@@ -69,3 +92,97 @@ impl ProgramInput {
         self.data.as_ref().unwrap().as_str()
     }
 }
+
+/// Finds a cycle in the sequence `initial, step(initial), step(step(initial)), ...`
+/// by remembering every state in a hash map.
+///
+/// Returns `(mu, lambda)`, where `mu` is the index of the first state that
+/// is part of the cycle, and `lambda` is the length of the cycle.
+/// `step` is called exactly once per state up to the first repeat, so it may
+/// have side effects, but memory grows linearly with `mu + lambda`.
+///
+/// The sequence must eventually repeat, otherwise this function does not return.
+pub fn find_cycle<T, F>(initial: T, mut step: F) -> (usize, usize)
+where T: Hash + Eq, F: FnMut(&T) -> T {
+    let mut history = HashMap::new();
+    let mut state = initial;
+    let mut i = 0;
+    loop {
+        if let Some(mu) = history.get(&state) {
+            return (*mu, i - mu);
+        }
+        let next = step(&state);
+        history.insert(state, i);
+        state = next;
+        i += 1;
+    }
+}
+
+/// Finds a cycle like `find_cycle`, but uses Brent's algorithm, which only
+/// keeps a constant number of states in memory.
+///
+/// In exchange, `step` is called several times for the same state,
+/// roughly `2 * mu + 3 * lambda` times in total.
+pub fn find_cycle_brent<T, F>(initial: T, mut step: F) -> (usize, usize)
+where T: Clone + Eq, F: FnMut(&T) -> T {
+    // Find lambda by letting the hare run ahead in increasing powers of two,
+    // teleporting the tortoise to the hare each time.
+    let mut power = 1;
+    let mut lambda = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == lambda {
+            tortoise = hare.clone();
+            power *= 2;
+            lambda = 0;
+        }
+        hare = step(&hare);
+        lambda += 1;
+    }
+
+    // Find mu by starting the hare lambda steps ahead of the tortoise,
+    // and advancing both until they meet.
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+    for _ in 0..lambda {
+        hare = step(&hare);
+    }
+    let mut mu = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        mu += 1;
+    }
+
+    (mu, lambda)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cycle() {
+        // Sequence: 0 1 2 3 4 5 6 2 3 ...
+        let step = |x: &usize| if *x == 6 { 2 } else { x + 1 };
+        let tests = vec![
+            (0, (2, 5)),
+            (2, (0, 5)),
+            (5, (0, 5)),
+        ];
+
+        for t in tests {
+            assert_eq!(find_cycle(t.0, step), t.1, "initial = {}", t.0);
+            assert_eq!(find_cycle_brent(t.0, step), t.1, "initial = {}", t.0);
+        }
+    }
+
+    #[test]
+    fn test_find_cycle_fixpoint() {
+        assert_eq!(find_cycle(7, |x| *x), (0, 1));
+        assert_eq!(find_cycle_brent(7, |x| *x), (0, 1));
+        assert_eq!(find_cycle(0, |x| if *x < 3 { x + 1 } else { *x }), (3, 1));
+        assert_eq!(find_cycle_brent(0, |x| if *x < 3 { x + 1 } else { *x }), (3, 1));
+    }
+}
@@ -0,0 +1,478 @@
+//! A generic tree, in which every node knows its parent.
+//!
+//! Nodes are reference-counted handles, so cloning a `Node` is cheap and
+//! yields another handle to the same node. Children are owned by their
+//! parent, while the link back to the parent is weak, so that dropping the
+//! root drops the whole tree.
+
+use std::rc::{Rc, Weak};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+
+pub struct Node<T> {
+    link: Rc<RefCell<Link<T>>>,
+}
+
+struct Link<T> {
+    parent: Option<Weak<RefCell<Link<T>>>>,
+    children: Vec<Node<T>>,
+    data: T,
+}
+
+impl<T> Node<T> {
+    /// Creates a new node without parent or children.
+    pub fn new(data: T) -> Self {
+        Node::from_link(Rc::new(RefCell::new(Link {
+            parent: None,
+            children: Vec::new(),
+            data,
+        })))
+    }
+
+    fn from_link(link: Rc<RefCell<Link<T>>>) -> Self {
+        Self { link }
+    }
+
+    pub fn data(&self) -> Ref<'_, T> {
+        Ref::map(self.link.borrow(), |l| &l.data)
+    }
+
+    pub fn data_mut(&self) -> RefMut<'_, T> {
+        RefMut::map(self.link.borrow_mut(), |l| &mut l.data)
+    }
+
+    /// Returns true if both handles refer to the same node.
+    pub fn ptr_eq(&self, other: &Node<T>) -> bool {
+        Rc::ptr_eq(&self.link, &other.link)
+    }
+
+    pub fn has_parent(&self) -> bool {
+        self.link.borrow().parent.is_some()
+    }
+
+    pub fn parent(&self) -> Option<Node<T>> {
+        self.link.borrow()
+            .parent
+            .clone()
+            .and_then(|p| p.upgrade())
+            .map(Node::from_link)
+    }
+
+    /// Returns the number of ancestors of this node, i.e., 0 for the root.
+    pub fn depth(&self) -> usize {
+        self.ancestors().count()
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.link.borrow().children.is_empty()
+    }
+
+    pub fn num_children(&self) -> usize {
+        self.link.borrow().children.len()
+    }
+
+    /// Returns the child at index `i`, if it exists.
+    pub fn child(&self, i: usize) -> Option<Node<T>> {
+        self.link.borrow().children.get(i).cloned()
+    }
+
+    /// Creates a new node with `data` and appends it to the children.
+    pub fn add_child(&mut self, data: T) -> Node<T> {
+        let child = Node::new(data);
+        // A new node cannot be an ancestor, so skip the checks of `append`.
+        self.attach(child.clone());
+        child
+    }
+
+    /// Appends an existing node, together with its subtree, to the children.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `child` already has a parent, or if it is
+    /// this node or one of its ancestors, which would create a cycle.
+    /// Checking the latter takes time proportional to the depth of this node.
+    pub fn append(&mut self, child: Node<T>) {
+        assert!(!child.has_parent(), "child node already has a parent");
+        assert!(
+            !self.ptr_eq(&child) && !self.ancestors().any(|a| a.ptr_eq(&child)),
+            "cannot append a node to itself or to one of its descendants"
+        );
+        self.attach(child);
+    }
+
+    fn attach(&mut self, child: Node<T>) {
+        child.link.borrow_mut().parent = Some(Rc::downgrade(&self.link));
+        self.link.borrow_mut().children.push(child);
+    }
+
+    pub fn children(&self) -> Children<T> {
+        Children {
+            parent: self.clone(),
+            next: 0,
+        }
+    }
+
+    /// Returns the other children of the parent, or `None` if this node
+    /// does not have a parent.
+    pub fn siblings(&self) -> Option<Siblings<T>> {
+        self.parent().map(|p| Siblings {
+            me: self.clone(),
+            children: p.children(),
+        })
+    }
+
+    /// Returns the parent, grandparent, and so on up to the root.
+    pub fn ancestors(&self) -> Ancestors<T> {
+        Ancestors { next: self.parent() }
+    }
+
+    /// Returns all nodes below this one in depth-first pre-order.
+    pub fn descendants(&self) -> DepthFirst<T> {
+        let mut iter = self.depth_first();
+        iter.next();
+        iter
+    }
+
+    /// Returns this node and all nodes below it in depth-first pre-order.
+    pub fn depth_first(&self) -> DepthFirst<T> {
+        DepthFirst { stack: vec![self.clone()] }
+    }
+
+    /// Returns this node and all nodes below it in depth-first post-order,
+    /// i.e., every node comes after all of its descendants.
+    pub fn post_order(&self) -> PostOrder<T> {
+        PostOrder { stack: vec![(self.clone(), false)] }
+    }
+
+    /// Returns this node and all nodes below it in breadth-first order.
+    pub fn breadth_first(&self) -> BreadthFirst<T> {
+        let mut queue = VecDeque::new();
+        queue.push_back(self.clone());
+        BreadthFirst { queue }
+    }
+
+    /// Folds the subtree bottom-up: `f` is called for every node with its
+    /// data and the results of its children, in order.
+    ///
+    /// This does not recurse, so it can be used on arbitrarily deep trees.
+    pub fn fold<B, F>(&self, mut f: F) -> B
+    where F: FnMut(&T, Vec<B>) -> B {
+        // Every node in the stack is paired with the number of results
+        // on the results stack when it was first seen.
+        let mut stack = vec![(self.clone(), None)];
+        let mut results: Vec<B> = Vec::new();
+        while let Some((node, mark)) = stack.pop() {
+            match mark {
+                None => {
+                    stack.push((node.clone(), Some(results.len())));
+                    let link = node.link.borrow();
+                    stack.extend(link.children.iter().rev().map(|c| (c.clone(), None)));
+                }
+                Some(n) => {
+                    let children = results.split_off(n);
+                    let b = f(&node.data(), children);
+                    results.push(b);
+                }
+            }
+        }
+        results.pop().unwrap()
+    }
+}
+
+impl<T> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        Node::from_link(self.link.clone())
+    }
+}
+
+impl<T> Drop for Node<T> {
+    /// Dropping the last handle to a node drops its children, which would
+    /// recurse once per level. Instead, the children of every node that is
+    /// about to go away are moved onto a stack and dropped from there.
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.link) != 1 {
+            return;
+        }
+        let mut stack = match self.link.try_borrow_mut() {
+            Ok(mut link) => mem::take(&mut link.children),
+            Err(_) => return,
+        };
+        while let Some(node) = stack.pop() {
+            if Rc::strong_count(&node.link) == 1 {
+                if let Ok(mut link) = node.link.try_borrow_mut() {
+                    stack.append(&mut link.children);
+                }
+            }
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Node<T> {
+    /// Two nodes are equal if their data and their children are equal.
+    fn eq(&self, other: &Node<T>) -> bool {
+        let mut stack = vec![(self.clone(), other.clone())];
+        while let Some((x, y)) = stack.pop() {
+            let a = x.link.borrow();
+            let b = y.link.borrow();
+            if a.data != b.data || a.children.len() != b.children.len() {
+                return false;
+            }
+            stack.extend(a.children.iter().cloned().zip(b.children.iter().cloned()));
+        }
+        true
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Node<T> {
+    /// Formats the subtree as `Node { data: .., children: [..] }`, without
+    /// recursing, so that deep trees can be printed as well.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Every node in the stack is paired with the number of its children
+        // that have already been written, or `None` if it was not opened yet.
+        let mut stack = vec![(self.clone(), None)];
+        while let Some((node, written)) = stack.pop() {
+            let link = node.link.borrow();
+            let i = match written {
+                None => {
+                    write!(f, "Node {{ data: {:?}, children: [", link.data)?;
+                    0
+                }
+                Some(i) => i,
+            };
+            match link.children.get(i) {
+                Some(child) => {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    let child = child.clone();
+                    drop(link);
+                    stack.push((node, Some(i + 1)));
+                    stack.push((child, None));
+                }
+                None => write!(f, "] }}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Children<T> {
+    parent: Node<T>,
+    next: usize,
+}
+
+impl<T> Iterator for Children<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let child = self.parent.child(self.next);
+        if child.is_some() {
+            self.next += 1;
+        }
+        child
+    }
+}
+
+pub struct Siblings<T> {
+    me: Node<T>,
+    children: Children<T>,
+}
+
+impl<T> Iterator for Siblings<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let me = &self.me;
+        self.children.find(|c| !c.ptr_eq(me))
+    }
+}
+
+pub struct Ancestors<T> {
+    next: Option<Node<T>>,
+}
+
+impl<T> Iterator for Ancestors<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next.take();
+        self.next = node.as_ref().and_then(|n| n.parent());
+        node
+    }
+}
+
+pub struct DepthFirst<T> {
+    stack: Vec<Node<T>>,
+}
+
+impl<T> Iterator for DepthFirst<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop();
+        if let Some(ref n) = node {
+            let link = n.link.borrow();
+            self.stack.extend(link.children.iter().rev().cloned());
+        }
+        node
+    }
+}
+
+pub struct PostOrder<T> {
+    stack: Vec<(Node<T>, bool)>,
+}
+
+impl<T> Iterator for PostOrder<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, expanded)) = self.stack.pop() {
+            if expanded {
+                return Some(node);
+            }
+            self.stack.push((node.clone(), true));
+            let link = node.link.borrow();
+            self.stack.extend(link.children.iter().rev().map(|c| (c.clone(), false)));
+        }
+        None
+    }
+}
+
+pub struct BreadthFirst<T> {
+    queue: VecDeque<Node<T>>,
+}
+
+impl<T> Iterator for BreadthFirst<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front();
+        if let Some(ref n) = node {
+            let link = n.link.borrow();
+            self.queue.extend(link.children.iter().cloned());
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the tree:
+    ///
+    /// ```text
+    /// 1 - 2 - 4
+    ///   \   \ 5
+    ///    3 - 6
+    /// ```
+    fn example() -> Node<u32> {
+        let mut root = Node::new(1);
+        let mut a = root.add_child(2);
+        let mut b = root.add_child(3);
+        a.add_child(4);
+        a.add_child(5);
+        b.add_child(6);
+        root
+    }
+
+    fn values<I: Iterator<Item = Node<u32>>>(iter: I) -> Vec<u32> {
+        iter.map(|n| *n.data()).collect()
+    }
+
+    #[test]
+    fn test_links() {
+        let root = example();
+        let a = root.child(0).unwrap();
+        let five = a.child(1).unwrap();
+        assert!(!root.has_parent());
+        assert!(a.parent().unwrap().ptr_eq(&root));
+        assert_eq!(five.depth(), 2);
+        assert!(five.is_leaf());
+        assert_eq!(root.num_children(), 2);
+        assert_eq!(values(root.children()), vec![2, 3]);
+        assert_eq!(values(five.siblings().unwrap()), vec![4]);
+        assert_eq!(values(a.siblings().unwrap()), vec![3]);
+        assert!(root.siblings().is_none());
+        assert_eq!(values(five.ancestors()), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_traversal() {
+        let root = example();
+        assert_eq!(values(root.depth_first()), vec![1, 2, 4, 5, 3, 6]);
+        assert_eq!(values(root.descendants()), vec![2, 4, 5, 3, 6]);
+        assert_eq!(values(root.post_order()), vec![4, 5, 2, 6, 3, 1]);
+        assert_eq!(values(root.breadth_first()), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_fold() {
+        let root = example();
+        let sum = root.fold(|x, cs: Vec<u32>| x + cs.iter().sum::<u32>());
+        assert_eq!(sum, 21);
+        let s = root.fold(|x, cs: Vec<String>| {
+            if cs.is_empty() { x.to_string() } else { format!("{}({})", x, cs.join(",")) }
+        });
+        assert_eq!(s, "1(2(4,5),3(6))");
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot append a node to itself")]
+    fn test_append_ancestor() {
+        let mut root = example();
+        let mut five = root.child(0).unwrap().child(1).unwrap();
+        five.append(root.clone());
+        root.append(Node::new(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot append a node to itself")]
+    fn test_append_self() {
+        let mut root = Node::new(1);
+        root.append(root.clone());
+    }
+
+    #[test]
+    fn test_deep() {
+        // Neither building, comparing, formatting nor dropping a deep tree
+        // may recurse once per level.
+        let n = 200_000;
+        let deep = || {
+            let root = Node::new(0);
+            let mut node = root.clone();
+            for i in 1..n {
+                node = node.add_child(i);
+            }
+            root
+        };
+        let root = deep();
+        assert_eq!(root.fold(|_, cs: Vec<usize>| cs.iter().sum::<usize>() + 1), n);
+        assert!(root == deep());
+        assert!(format!("{:?}", root).ends_with(&"] }".repeat(n)));
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut root = Node::new(1);
+        root.add_child(2).add_child(3);
+        root.add_child(4);
+        assert_eq!(format!("{:?}", root), "Node { data: 1, children: [\
+            Node { data: 2, children: [Node { data: 3, children: [] }] }, \
+            Node { data: 4, children: [] }] }");
+    }
+
+    #[test]
+    fn test_data_mut() {
+        let root = example();
+        root.descendants().for_each(|n| *n.data_mut() *= 10);
+        assert_eq!(values(root.depth_first()), vec![1, 20, 40, 50, 30, 60]);
+        assert_eq!(root, {
+            let r = example();
+            r.descendants().for_each(|n| *n.data_mut() *= 10);
+            r
+        });
+    }
+}